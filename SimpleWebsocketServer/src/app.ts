@@ -3,31 +3,67 @@ import WebSocket, { WebSocketServer } from 'ws';
 const PORT = process.env.PORT ? parseInt(process.env.PORT) : 8080;
 interface User {
     ws: WebSocket;
-    nick: String;
+    nick: string;
     isAlive: boolean;
 }
 
 interface Message {
     messageType: String;
-    data: String;
+    data: string;
     dataArray: String[];
 }
 
 let users: User[] = [];
 
+// Same rules as `validate_nick` in YewChatProtocol.
+const NICK_MIN_LEN = 2;
+const NICK_MAX_LEN = 20;
+const RESERVED_NICKS = ['admin', 'moderator', 'server', 'system', 'yewchat'];
+
+const checkNick = (ws: WebSocket, nick: string): string | null => {
+    const chars = Array.from(nick ?? '');
+    if (chars.length < NICK_MIN_LEN) return `Nama minimal ${NICK_MIN_LEN} karakter`;
+    if (chars.length > NICK_MAX_LEN) return `Nama maksimal ${NICK_MAX_LEN} karakter`;
+    const bad = chars.find((c) => !/^[A-Za-z0-9_.-]$/.test(c));
+    if (bad !== undefined) return `Karakter '${bad}' tidak boleh dipakai; gunakan huruf, angka, _, - atau .`;
+    if (RESERVED_NICKS.includes(nick.toLowerCase())) return 'Nama ini dicadangkan';
+    if (users.some((u) => u.ws !== ws && u.nick.toLowerCase() === nick.toLowerCase())) return 'Nama ini sedang dipakai';
+    return null;
+};
+
 console.log(`Listening on port ${PORT}`);
 const wss = new WebSocketServer({ port: PORT });
 
 wss.on('connection', (ws: WebSocket) => {
     console.log('ws connected');
 
+    // Free the nickname right away instead of waiting for the next ping, so
+    // the same user can register it again when reconnecting.
+    ws.on('close', () => {
+        if (users.some((u) => u.ws === ws)) {
+            users = users.filter((u) => u.ws !== ws);
+            broadcast(JSON.stringify({ messageType: 'users', dataArray: users.map((u) => u.nick) }));
+        }
+    });
+
     ws.on('message', (data) => {
         const raw_data = data.toString();
         try {
             const parsed_data: Message = JSON.parse(raw_data);
             switch (parsed_data.messageType) {
                 case 'register':
-                    users.push({ ws, nick: parsed_data.data, isAlive: true });
+                    const reason = checkNick(ws, parsed_data.data);
+                    if (reason) {
+                        ws.send(JSON.stringify({ messageType: 'registerRejected', data: reason }));
+                        break;
+                    }
+                    const existing = users.find((u) => u.ws === ws);
+                    if (existing) {
+                        existing.nick = parsed_data.data;
+                    } else {
+                        users.push({ ws, nick: parsed_data.data, isAlive: true });
+                    }
+                    ws.send(JSON.stringify({ messageType: 'registerAck', data: parsed_data.data }));
                     broadcast(JSON.stringify({ messageType: 'users', dataArray: users.map((u) => u.nick) }));
                     break;
                 case 'message':
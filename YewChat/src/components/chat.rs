@@ -1,136 +1,343 @@
-use serde::{Deserialize, Serialize};
+use gloo_events::EventListener;
+use gloo_timers::callback::{Interval, Timeout};
 use web_sys::HtmlInputElement;
 use yew::prelude::*;
 use yew_agent::{Bridge, Bridged};
+use yew_router::prelude::*;
+use yewchat_protocol::{
+    segments, unlink_mentions, validate_room, MessageData, Segment, LOBBY, REACTIONS,
+};
 
-use crate::services::event_bus::EventBus;
-use crate::{services::websocket::WebsocketService, User};
+use crate::components::guard::LoginQuery;
+use crate::components::timestamp::{day_key, day_label, Timestamp};
+use crate::services::connection::Connection;
+use crate::services::event_bus::{Event, EventBus};
+use crate::services::outbox::{Delivery, OutboxEvent};
+use crate::services::session;
+use crate::services::websocket::ConnectionState;
+use crate::state::{
+    complete_mention, reactors, Action, ChatState, Command, Conversation, LocalMessage,
+};
+use crate::store::{AppState, Store, Subscription};
+use crate::Route;
 
 pub enum Msg {
-    HandleMsg(String),
+    Action(Action),
+    Store(AppState),
+    ReconnectNow,
+    /// The chat input changed to the given text.
+    ChatInput(String),
+    /// Complete the mention being typed to the given nickname.
+    Complete(String),
     SubmitMessage,
+    SubmitReply,
+    /// Start editing one of our messages in place, by id.
+    StartEdit(u64),
+    SaveEdit,
+    CancelEdit,
+    /// Show or hide the edit history of a message, by id.
+    ToggleHistory(u64),
+    /// The room form was submitted.
+    OpenRoom,
+    LeaveRoom,
+    Tick,
+    /// Time to sweep stale typing indicators and end our own idle typing.
+    CheckTyping,
+    /// The window gained or lost focus.
+    Focus(bool),
+    /// The session token ran out.
+    Expired,
 }
 
-#[derive(Deserialize)]
-struct MessageData {
-    from: String,
-    message: String,
+/// How often relative timestamps are refreshed.
+const CLOCK_INTERVAL_MS: u32 = 30_000;
+/// How often typing indicators are checked for going stale.
+const TYPING_CHECK_MS: u32 = 1_000;
+/// Readers shown under a message before the rest are only counted.
+const MAX_READERS: usize = 5;
+
+/// Where a message is shown, which decides what can be done with it there.
+#[derive(Clone, Copy, PartialEq)]
+enum Place {
+    /// In the timeline, with what it takes to open its thread.
+    Timeline,
+    /// Heading the open thread. It is a copy of the one in the timeline, so
+    /// it is only edited or deleted there.
+    Root,
+    /// Among the replies of the open thread.
+    Reply,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "lowercase")]
-pub enum MsgTypes {
-    Users,
-    Register,
-    Message,
+#[derive(Properties, PartialEq)]
+pub struct ChatProps {
+    /// The room to show.
+    #[prop_or_else(lobby)]
+    pub room: String,
+    /// The nickname to talk to in private, shown instead of `room`.
+    #[prop_or_default]
+    pub direct: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct WebSocketMessage {
-    message_type: MsgTypes,
-    data_array: Option<Vec<String>>,
-    data: Option<String>,
+impl ChatProps {
+    fn action(&self) -> Action {
+        match &self.direct {
+            Some(nick) => Action::Direct(nick.clone()),
+            None => Action::View(self.room.clone()),
+        }
+    }
 }
 
-#[derive(Clone)]
-struct UserProfile {
-    name: String,
-    avatar: String,
+fn lobby() -> String {
+    LOBBY.to_owned()
 }
 
 pub struct Chat {
-    users: Vec<UserProfile>,
+    state: ChatState,
     chat_input: NodeRef,
+    /// Nicknames offered for the `@` being typed in the chat input.
+    suggestions: Vec<String>,
+    thread_input: NodeRef,
+    /// Id of the message being edited in place, if any.
+    editing: Option<u64>,
+    edit_input: NodeRef,
+    /// Id of the message whose edit history is shown, if any.
+    history: Option<u64>,
+    room_input: NodeRef,
+    /// Why the name in the room form was refused.
+    room_error: Option<String>,
     _producer: Box<dyn Bridge<EventBus>>,
-    wss: WebsocketService,
-    messages: Vec<MessageData>,
+    store: Store,
+    _store: Subscription,
+    connection: Connection,
+    /// Current time in milliseconds, advanced by `_clock`.
+    now: f64,
+    _clock: Interval,
+    _typing_clock: Interval,
+    /// Whether the window has focus, so what is on screen counts as read.
+    focused: bool,
+    _focus: [EventListener; 2],
+    /// Fires when the session token runs out.
+    _expiry: Option<Timeout>,
 }
 
 impl Component for Chat {
     type Message = Msg;
-    type Properties = ();
+    type Properties = ChatProps;
 
     fn create(ctx: &Context<Self>) -> Self {
-        let (user, _) = ctx
+        let (store, _) = ctx
             .link()
-            .context::<User>(Callback::noop())
+            .context::<Store>(Callback::noop())
             .expect("context to be set");
-        let wss = WebsocketService::new();
-        let username = user.username.borrow().clone();
+        let (connection, _) = ctx
+            .link()
+            .context::<Connection>(Callback::noop())
+            .expect("context to be set");
+        let app = store.get();
+        let expiry = app.token.as_ref().map(|token| {
+            let link = ctx.link().clone();
+            let left = token.expires_at as f64 - js_sys::Date::now();
+            let left = left.clamp(0.0, u32::MAX as f64);
+            Timeout::new(left as u32, move || link.send_message(Msg::Expired))
+        });
 
-        let message = WebSocketMessage {
-            message_type: MsgTypes::Register,
-            data: Some(username.to_string()),
-            data_array: None,
+        let mut chat = Self {
+            state: ChatState::new(app.user.unwrap_or_default(), app.connection),
+            now: js_sys::Date::now(),
+            _clock: {
+                let link = ctx.link().clone();
+                Interval::new(CLOCK_INTERVAL_MS, move || link.send_message(Msg::Tick))
+            },
+            _typing_clock: {
+                let link = ctx.link().clone();
+                Interval::new(TYPING_CHECK_MS, move || link.send_message(Msg::CheckTyping))
+            },
+            focused: web_sys::window()
+                .and_then(|window| window.document())
+                .is_some_and(|document| document.has_focus().unwrap_or(false)),
+            _focus: {
+                let window = web_sys::window().expect("no window");
+                let (focus, blur) = (ctx.link().clone(), ctx.link().clone());
+                [
+                    EventListener::new(&window, "focus", move |_| {
+                        focus.send_message(Msg::Focus(true))
+                    }),
+                    EventListener::new(&window, "blur", move |_| {
+                        blur.send_message(Msg::Focus(false))
+                    }),
+                ]
+            },
+            chat_input: NodeRef::default(),
+            suggestions: vec![],
+            thread_input: NodeRef::default(),
+            editing: None,
+            edit_input: NodeRef::default(),
+            history: None,
+            room_input: NodeRef::default(),
+            room_error: None,
+            connection,
+            _producer: EventBus::bridge(ctx.link().batch_callback(|event| match event {
+                Event::Frame(s) => Some(Msg::Action(Action::Frame(s))),
+                Event::Outbox(event) => Some(Msg::Action(Action::Outbox(event))),
+                // Connection changes come from the store instead.
+                Event::Connection(_) => None,
+            })),
+            _store: store.subscribe(ctx.link().callback(Msg::Store)),
+            store,
+            _expiry: expiry,
         };
+        chat.dispatch(Action::Tick(chat.now));
+        chat.dispatch(Action::Sync);
+        chat.open(ctx);
+        chat
+    }
 
-        if let Ok(_) = wss
-            .tx
-            .clone()
-            .try_send(serde_json::to_string(&message).unwrap())
-        {
-            log::debug!("message sent successfully");
-        }
-
-        Self {
-            users: vec![],
-            messages: vec![],
-            chat_input: NodeRef::default(),
-            wss,
-            _producer: EventBus::bridge(ctx.link().callback(Msg::HandleMsg)),
-        }
+    fn changed(&mut self, ctx: &Context<Self>) -> bool {
+        self.editing = None;
+        self.history = None;
+        self.open(ctx);
+        true
     }
 
-    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
-            Msg::HandleMsg(s) => {
-                let msg: WebSocketMessage = serde_json::from_str(&s).unwrap();
-                match msg.message_type {
-                    MsgTypes::Users => {
-                        let users_from_message = msg.data_array.unwrap_or_default();
-                        self.users = users_from_message
-                            .iter()
-                            .map(|u| UserProfile {
-                                name: u.into(),
-                                avatar: format!(
-                                    "https://avatars.dicebear.com/api/adventurer-neutral/{}.svg",
-                                    u
-                                )
-                                .into(),
-                            })
-                            .collect();
-                        return true;
-                    }
-                    MsgTypes::Message => {
-                        let message_data: MessageData =
-                            serde_json::from_str(&msg.data.unwrap()).unwrap();
-                        self.messages.push(message_data);
-                        return true;
-                    }
-                    _ => {
-                        return false;
-                    }
+            Msg::Action(action) => {
+                self.dispatch(action);
+                true
+            }
+            Msg::Store(app) => {
+                if app.connection == self.state.status {
+                    return false;
                 }
+                // The server refused our token, whatever our own clock says.
+                if app.connection.state == ConnectionState::Expired {
+                    ctx.link().send_message(Msg::Expired);
+                }
+                self.dispatch(Action::Connection(app.connection));
+                true
+            }
+            Msg::ReconnectNow => {
+                self.connection.reconnect_now();
+                false
+            }
+            Msg::ChatInput(text) => {
+                self.suggestions = self
+                    .state
+                    .suggestions(&text)
+                    .into_iter()
+                    .map(Into::into)
+                    .collect();
+                self.dispatch(Action::Input(text, js_sys::Date::now()));
+                true
+            }
+            Msg::Complete(nick) => {
+                let Some(input) = self.chat_input.cast::<HtmlInputElement>() else {
+                    return false;
+                };
+                input.set_value(&complete_mention(&input.value(), &nick));
+                let _ = input.focus();
+                self.suggestions.clear();
+                true
             }
             Msg::SubmitMessage => {
                 let input = self.chat_input.cast::<HtmlInputElement>();
                 if let Some(input) = input {
-                    let message = WebSocketMessage {
-                        message_type: MsgTypes::Message,
-                        data: Some(input.value()),
-                        data_array: None,
-                    };
-                    if let Err(e) = self
-                        .wss
-                        .tx
-                        .clone()
-                        .try_send(serde_json::to_string(&message).unwrap())
-                    {
-                        log::debug!("error sending to channel: {:?}", e);
-                    }
+                    self.dispatch(Action::Submit(input.value()));
                     input.set_value("");
                 };
+                self.suggestions.clear();
+                true
+            }
+            Msg::SubmitReply => {
+                let input = self.thread_input.cast::<HtmlInputElement>();
+                if let Some(input) = input {
+                    self.dispatch(Action::Reply(input.value()));
+                    input.set_value("");
+                };
+                true
+            }
+            Msg::StartEdit(id) => {
+                self.editing = Some(id);
+                true
+            }
+            Msg::SaveEdit => {
+                let (Some(id), Some(input)) =
+                    (self.editing, self.edit_input.cast::<HtmlInputElement>())
+                else {
+                    return false;
+                };
+                self.dispatch(Action::Edit(id, input.value()));
+                self.editing = None;
+                true
+            }
+            Msg::CancelEdit => {
+                self.editing = None;
+                true
+            }
+            Msg::ToggleHistory(id) => {
+                self.history = if self.history == Some(id) {
+                    None
+                } else {
+                    Some(id)
+                };
+                true
+            }
+            Msg::OpenRoom => {
+                let Some(input) = self.room_input.cast::<HtmlInputElement>() else {
+                    return false;
+                };
+                let id = input.value().trim().to_owned();
+                if let Err(e) = validate_room(&id) {
+                    self.room_error = Some(e.to_string());
+                    return true;
+                }
+                self.room_error = None;
+                input.set_value("");
+                if let Some(history) = ctx.link().history() {
+                    history.push(Route::Room { id });
+                }
+                true
+            }
+            Msg::LeaveRoom => {
+                self.dispatch(Action::Leave(ctx.props().room.clone()));
+                if let Some(history) = ctx.link().history() {
+                    history.push(Route::Chat);
+                }
+                true
+            }
+            Msg::Tick => {
+                self.now = js_sys::Date::now();
+                true
+            }
+            Msg::Focus(focused) => {
+                self.focused = focused;
+                // Coming back counts as reading what is on screen.
+                if focused {
+                    self.dispatch(Action::Seen);
+                }
+                false
+            }
+            Msg::CheckTyping => {
+                let before: Vec<String> = self.state.typing().into_iter().map(Into::into).collect();
+                self.dispatch(Action::Tick(js_sys::Date::now()));
+                self.state.typing() != before
+            }
+            Msg::Expired => {
+                session::clear();
+                self.connection.disconnect();
+                if let Some(history) = ctx.link().history() {
+                    let query = LoginQuery {
+                        next: Some(history.location().pathname()),
+                        expired: true,
+                    };
+                    if let Err(e) = history.replace_with_query(Route::Login, query) {
+                        log::warn!("cannot return to login: {}", e);
+                    }
+                }
+                self.store.update(|state| {
+                    state.user = None;
+                    state.token = None;
+                });
                 false
             }
         }
@@ -138,20 +345,37 @@ impl Component for Chat {
 
     fn view(&self, ctx: &Context<Self>) -> Html {
         let submit = ctx.link().callback(|_| Msg::SubmitMessage);
+        let chat_input = ctx.link().callback(|e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            Msg::ChatInput(input.value())
+        });
+        let first = self.suggestions.first().cloned();
+        let complete = ctx.link().batch_callback(move |e: KeyboardEvent| {
+            let nick = first.clone().filter(|_| e.key() == "Tab")?;
+            e.prevent_default();
+            Some(Msg::Complete(nick))
+        });
+        let messages = self.state.timeline();
+        let readers = self.state.read_markers();
         html! {
             <div class="flex w-screen h-screen bg-gradient-to-br from-purple-50 to-blue-50">
-                // Sidebar untuk daftar pengguna
-                <div class="flex-none w-72 h-screen bg-gradient-to-b from-indigo-600 to-purple-700 shadow-2xl">
+                // Sidebar untuk daftar ruang dan anggotanya
+                <div class="flex-none w-72 h-screen overflow-auto bg-gradient-to-b from-indigo-600 to-purple-700 shadow-2xl">
                     <div class="text-2xl font-bold p-4 text-white border-b border-indigo-500/30">
                         <div class="flex items-center gap-3">
-                            <div class="w-3 h-3 bg-green-400 rounded-full animate-pulse"></div>
+                            <div class={classes!("w-3", "h-3", "rounded-full", status_dot(&self.state.status.state))}></div>
                             {"👥 Pengguna Online"}
                         </div>
                     </div>
+                    {self.view_rooms(ctx)}
+                    {self.view_directs()}
+                    <div class="px-4 pt-4 text-xs font-semibold uppercase tracking-wide text-indigo-200">
+                        {format!("Anggota #{}", self.state.room)}
+                    </div>
                     <div class="p-4 space-y-3">
                         {
-                            self.users.clone().iter().map(|u| {
-                                html!{
+                            self.state.rooms[&self.state.room].members.iter().map(|u| {
+                                let card = html!{
                                     <div class="flex items-center gap-3 bg-white/10 backdrop-blur-sm rounded-xl p-3 hover:bg-white/20 transition-all duration-200 cursor-pointer border border-white/20">
                                         <div class="relative">
                                             <img class="w-12 h-12 rounded-full border-2 border-white/50" src={u.avatar.clone()} alt="avatar"/>
@@ -166,6 +390,12 @@ impl Component for Chat {
                                             </div>
                                         </div>
                                     </div>
+                                };
+                                if u.name == self.state.username {
+                                    return card;
+                                }
+                                html! {
+                                    <Link<Route> to={Route::Direct { nick: u.name.clone() }}>{card}</Link<Route>>
                                 }
                             }).collect::<Html>()
                         }
@@ -179,52 +409,92 @@ impl Component for Chat {
                         <div class="flex items-center h-full px-6">
                             <div class="text-2xl">{"💬"}</div>
                             <div class="ml-3">
-                                <div class="text-xl font-bold text-gray-800">{"Ruang Obrolan"}</div>
+                                <div class="text-xl font-bold text-gray-800">{self.title()}</div>
                                 <div class="text-sm text-gray-500">
-                                    {format!("{} pengguna aktif", self.users.len())}
+                                    if self.state.direct.is_some() {
+                                        {format!("Pesan langsung · {} pengguna aktif", self.state.users.len())}
+                                    } else {
+                                        {format!("{} anggota · {} pengguna aktif", self.state.current().members.len(), self.state.users.len())}
+                                    }
+                                    if self.state.decode_errors > 0 {
+                                        <span class="ml-2 text-amber-500" title="Lihat console untuk detail">
+                                            {format!("· {} pesan tidak terbaca", self.state.decode_errors)}
+                                        </span>
+                                    }
                                 </div>
                             </div>
+                            <div class="ml-auto flex items-center gap-4">
+                                {self.view_connection(ctx)}
+                                <button onclick={ctx.link().callback(|_| Msg::Action(Action::ToggleMentions))} title="Hanya pesan yang menyebut Anda" class={classes!("px-3", "py-1", "rounded-full", "border", "text-xs", "font-semibold", if self.state.mentions_only { "border-amber-400 bg-amber-100 text-amber-700" } else { "border-gray-300 text-gray-600 hover:bg-gray-100" })}>
+                                    {"@ Sebutan"}
+                                </button>
+                                if self.state.direct.is_none() && self.state.room != LOBBY {
+                                    <button onclick={ctx.link().callback(|_| Msg::LeaveRoom)} class="px-3 py-1 rounded-full border border-gray-300 text-gray-600 hover:bg-gray-100 text-xs font-semibold">
+                                        {"Tinggalkan ruang"}
+                                    </button>
+                                }
+                                <Link<Route> to={Route::Logout}>
+                                    <button class="px-3 py-1 rounded-full border border-gray-300 text-gray-600 hover:bg-gray-100 text-xs font-semibold">
+                                        {"Keluar"}
+                                    </button>
+                                </Link<Route>>
+                            </div>
                         </div>
                     </div>
                     
                     // Area pesan
                     <div class="w-full grow overflow-auto p-4 space-y-4 bg-gradient-to-b from-gray-50 to-white">
+                        if self.state.mentions_only && messages.is_empty() {
+                            <div class="text-center text-sm text-gray-400 pt-8">{"Belum ada yang menyebut Anda di sini"}</div>
+                        }
                         {
-                            self.messages.iter().map(|m| {
-                                let user = self.users.iter().find(|u| u.name == m.from).unwrap();
-                                html!{
-                                    <div class="flex items-start gap-3 max-w-4xl">
-                                        <img class="w-10 h-10 rounded-full border-2 border-indigo-200 shadow-sm" src={user.avatar.clone()} alt="avatar"/>
-                                        <div class="bg-white rounded-2xl rounded-tl-md shadow-md p-4 border border-gray-100 flex-grow">
-                                            <div class="flex items-center gap-2 mb-2">
-                                                <div class="text-sm font-semibold text-indigo-600">
-                                                    {m.from.clone()}
-                                                </div>
-                                                <div class="text-xs text-gray-400">
-                                                    {"baru saja"}
-                                                </div>
+                            messages.iter().enumerate().map(|(i, m)| {
+                                let time = m.time as f64;
+                                let new_day = i == 0
+                                    || day_key(messages[i - 1].time as f64) != day_key(time);
+                                html! {
+                                    <>
+                                        if new_day {
+                                            <div class="flex items-center gap-3 text-xs text-gray-400 max-w-4xl">
+                                                <div class="grow border-t border-gray-200"></div>
+                                                {day_label(time, self.now)}
+                                                <div class="grow border-t border-gray-200"></div>
                                             </div>
-                                            <div class="text-gray-700">
-                                                if m.message.ends_with(".gif") {
-                                                    <img class="mt-2 rounded-lg max-w-sm shadow-sm" src={m.message.clone()} alt="GIF"/>
-                                                } else {
-                                                    <div class="break-words">
-                                                        {m.message.clone()}
-                                                    </div>
-                                                }
-                                            </div>
-                                        </div>
-                                    </div>
+                                        }
+                                        {self.view_message(ctx, m, Place::Timeline)}
+                                        if let Some(readers) = m.id.and_then(|id| readers.get(&id)) {
+                                            {self.view_readers(readers)}
+                                        }
+                                    </>
                                 }
                             }).collect::<Html>()
                         }
+                        { for self.state.outbox.iter().filter(|m| !self.state.mentions_only && m.to == self.state.conversation() && m.parent_id.is_none()).map(|m| self.view_local(ctx, m)) }
                     </div>
                     
                     // Input area
                     <div class="w-full bg-white border-t border-gray-200 shadow-lg">
-                        <div class="flex items-center gap-4 p-4">
+                        <div class="h-5 px-8 pt-1 text-xs italic text-gray-500">
+                            {typing_label(&self.state.typing())}
+                        </div>
+                        <div class="relative flex items-center gap-4 px-4 pb-4">
+                            if !self.suggestions.is_empty() {
+                                <div class="absolute bottom-full left-4 mb-2 w-64 bg-white rounded-xl shadow-lg border border-gray-200 py-1">
+                                    { for self.suggestions.iter().map(|nick| {
+                                        let pick = nick.clone();
+                                        html! {
+                                            <button onclick={ctx.link().callback(move |_| Msg::Complete(pick.clone()))} class="w-full flex items-center gap-2 px-3 py-2 text-sm text-gray-700 hover:bg-indigo-50">
+                                                <img class="w-6 h-6 rounded-full" src={self.state.avatar(nick)} alt="avatar"/>
+                                                {format!("@{}", nick)}
+                                            </button>
+                                        }
+                                    }) }
+                                </div>
+                            }
                             <input 
                                 ref={self.chat_input.clone()} 
+                                oninput={chat_input}
+                                onkeydown={complete}
                                 type="text" 
                                 placeholder="Ketik pesan Anda di sini..." 
                                 class="flex-grow py-3 px-4 bg-gray-50 border border-gray-200 rounded-full outline-none focus:ring-2 focus:ring-indigo-500 focus:border-transparent transition-all duration-200 text-gray-700 placeholder-gray-400"
@@ -233,6 +503,7 @@ impl Component for Chat {
                             />
                             <button 
                                 onclick={submit} 
+                                aria-label="Kirim"
                                 class="p-3 bg-gradient-to-r from-indigo-500 to-purple-600 hover:from-indigo-600 hover:to-purple-700 w-12 h-12 rounded-full flex justify-center items-center shadow-lg hover:shadow-xl transition-all duration-200 transform hover:scale-105"
                             >
                                 <svg fill="#ffffff" viewBox="0 0 24 24" xmlns="http://www.w3.org/2000/svg" class="w-5 h-5">
@@ -243,7 +514,487 @@ impl Component for Chat {
                         </div>
                     </div>
                 </div>
+                {self.view_thread(ctx)}
+            </div>
+        }
+    }
+
+    /// Whatever was just shown counts as read while the window has focus.
+    fn rendered(&mut self, _ctx: &Context<Self>, _first_render: bool) {
+        if self.focused {
+            self.dispatch(Action::Seen);
+        }
+    }
+}
+
+fn status_dot(state: &ConnectionState) -> &'static str {
+    match state {
+        ConnectionState::Open => "bg-green-400 animate-pulse",
+        ConnectionState::Connecting | ConnectionState::Reconnecting { .. } => {
+            "bg-amber-400 animate-pulse"
+        }
+        ConnectionState::Closed | ConnectionState::Expired => "bg-red-400",
+    }
+}
+
+/// Who is typing, as shown above the input.
+fn typing_label(names: &[&str]) -> String {
+    match names {
+        [] => String::new(),
+        [name] => format!("{} sedang mengetik…", name),
+        [first, second] => format!("{} dan {} sedang mengetik…", first, second),
+        _ => format!("{} orang sedang mengetik…", names.len()),
+    }
+}
+
+/// The text of a chat line, or the picture it links to.
+fn view_text(message: &str, me: &str) -> Html {
+    html! {
+        if message.ends_with(".gif") {
+            <img class="mt-2 rounded-lg max-w-sm shadow-sm" src={message.to_owned()} alt="GIF"/>
+        } else {
+            <div class="break-words">
+                { for segments(message).into_iter().map(|segment| match segment {
+                    Segment::Text(text) => html! { {text.to_owned()} },
+                    Segment::Mention(nick) => html! {
+                        <span class={classes!("font-semibold", "text-indigo-600", (nick == me).then_some("bg-amber-100 rounded px-1"))}>
+                            {format!("@{}", nick)}
+                        </span>
+                    },
+                }) }
+            </div>
+        }
+    }
+}
+
+impl Chat {
+    /// Runs `action` through the reducer and carries out its commands.
+    /// Opens the conversation the route names. A room name the server would
+    /// refuse, typed into the address bar, sends us back to the lobby with the
+    /// reason under the room box instead.
+    fn open(&mut self, ctx: &Context<Self>) {
+        let props = ctx.props();
+        if props.direct.is_none() {
+            if let Err(e) = validate_room(&props.room) {
+                self.room_error = Some(format!("Tidak bisa membuka {:?}: {}", props.room, e));
+                if let Some(history) = ctx.link().history() {
+                    history.replace(Route::Chat);
+                }
+                return;
+            }
+        }
+        self.dispatch(props.action());
+    }
+
+    fn dispatch(&mut self, action: Action) {
+        for command in self.state.reduce(action) {
+            match command {
+                Command::Send(message) => {
+                    if let Err(entry) = self.connection.send(&message) {
+                        log::debug!("error sending to channel: {}", entry.id);
+                        let id = entry.id;
+                        self.state
+                            .reduce(Action::Outbox(OutboxEvent::Queued(entry)));
+                        self.state
+                            .reduce(Action::Outbox(OutboxEvent::Delivery(id, Delivery::Failed)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reports every change to an input box, so the others see us typing.
+    fn typing_callback(&self, ctx: &Context<Self>) -> Callback<InputEvent> {
+        ctx.link().callback(|e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            Msg::Action(Action::Input(input.value(), js_sys::Date::now()))
+        })
+    }
+
+    fn view_rooms(&self, ctx: &Context<Self>) -> Html {
+        let open = ctx.link().callback(|e: FocusEvent| {
+            e.prevent_default();
+            Msg::OpenRoom
+        });
+        let rooms = self.state.room_list().into_iter().map(|id| {
+            let unread = self.state.rooms.get(&id).map_or(0, |r| r.unread);
+            let active = self.state.direct.is_none() && id == self.state.room;
+            html! {
+                <Link<Route> to={Route::Room { id: id.clone() }}>
+                    <div class={classes!("flex", "items-center", "rounded-lg", "px-3", "py-2", "text-sm", "text-white", "hover:bg-white/20", active.then_some("bg-white/20 font-semibold"))}>
+                        {format!("# {}", id)}
+                        if unread > 0 {
+                            <span class="ml-auto rounded-full bg-red-500 px-2 text-xs font-bold" title="Pesan belum dibaca">
+                                {unread}
+                            </span>
+                        }
+                    </div>
+                </Link<Route>>
+            }
+        });
+
+        html! {
+            <>
+                <div class="px-4 pt-4 text-xs font-semibold uppercase tracking-wide text-indigo-200">
+                    {"Ruang"}
+                </div>
+                <div class="px-4 pt-2 space-y-1">
+                    { for rooms }
+                </div>
+                <form onsubmit={open} class="px-4 pt-2 flex gap-2">
+                    <input ref={self.room_input.clone()} name="room" placeholder="nama-ruang" class="min-w-0 grow rounded-lg px-3 py-1 text-sm text-gray-800 bg-white" />
+                    <button type="submit" class="rounded-lg bg-white/20 px-3 text-sm font-semibold text-white hover:bg-white/30">
+                        {"Masuk"}
+                    </button>
+                </form>
+                if let Some(error) = &self.room_error {
+                    <div class="px-4 pt-1 text-xs text-red-200">{error.clone()}</div>
+                }
+            </>
+        }
+    }
+
+    /// The direct conversations, with whoever wrote last on top.
+    fn view_directs(&self) -> Html {
+        let mut directs: Vec<_> = self.state.directs.iter().collect();
+        directs.sort_by_key(|(_, chat)| std::cmp::Reverse(chat.messages.last().map(|m| m.time)));
+        let directs = directs.into_iter().map(|(nick, chat)| {
+            let active = self.state.direct.as_ref() == Some(nick);
+            html! {
+                <Link<Route> to={Route::Direct { nick: nick.clone() }}>
+                    <div class={classes!("flex", "items-center", "gap-2", "rounded-lg", "px-3", "py-2", "text-sm", "text-white", "hover:bg-white/20", active.then_some("bg-white/20 font-semibold"))}>
+                        <img class="w-6 h-6 rounded-full border border-white/50" src={self.state.avatar(nick)} alt="avatar"/>
+                        {nick.clone()}
+                        if chat.unread > 0 {
+                            <span class="ml-auto rounded-full bg-red-500 px-2 text-xs font-bold" title="Pesan belum dibaca">
+                                {chat.unread}
+                            </span>
+                        }
+                    </div>
+                </Link<Route>>
+            }
+        });
+
+        html! {
+            <>
+                <div class="px-4 pt-4 text-xs font-semibold uppercase tracking-wide text-indigo-200">
+                    {"Pesan Langsung"}
+                </div>
+                <div class="px-4 pt-2 space-y-1">
+                    if self.state.directs.is_empty() {
+                        <div class="px-3 text-xs text-indigo-200">{"Klik seorang anggota untuk mulai."}</div>
+                    }
+                    { for directs }
+                </div>
+            </>
+        }
+    }
+
+    /// What the header calls the conversation on screen.
+    fn title(&self) -> String {
+        match self.state.conversation() {
+            Conversation::Room(room) => format!("#{}", room),
+            Conversation::Direct(nick) => format!("@{}", nick),
+        }
+    }
+
+    fn view_connection(&self, ctx: &Context<Self>) -> Html {
+        let label = match &self.state.status.state {
+            ConnectionState::Connecting => "Menghubungkan…".to_string(),
+            ConnectionState::Open => "Terhubung".to_string(),
+            ConnectionState::Reconnecting { attempt, delay_ms } => format!(
+                "Terputus, mencoba lagi dalam {} dtk (percobaan ke-{})",
+                delay_ms.div_ceil(1000),
+                attempt
+            ),
+            ConnectionState::Closed => "Koneksi ditutup".to_string(),
+            ConnectionState::Expired => "Sesi berakhir".to_string(),
+        };
+        let reconnect = ctx.link().callback(|_| Msg::ReconnectNow);
+        let offline = self.state.status.state != ConnectionState::Open;
+
+        html! {
+            <div class="flex items-center gap-3 text-sm">
+                <div class="text-right">
+                    <div class="flex items-center justify-end gap-2 text-gray-600">
+                        <div class={classes!("w-2", "h-2", "rounded-full", status_dot(&self.state.status.state))}></div>
+                        {label}
+                    </div>
+                    if let (true, Some(error)) = (offline, &self.state.status.last_error) {
+                        <div class="text-xs text-red-400 truncate max-w-xs" title={error.clone()}>
+                            {error.clone()}
+                        </div>
+                    }
+                    if let Some(reason) = &self.state.rejected {
+                        <div class="text-xs text-red-400 truncate max-w-xs" title={reason.clone()}>
+                            {format!("Nama ditolak: {}", reason)}
+                        </div>
+                    }
+                </div>
+                if matches!(self.state.status.state, ConnectionState::Reconnecting { .. }) {
+                    <button onclick={reconnect} class="px-3 py-1 rounded-full bg-indigo-500 hover:bg-indigo-600 text-white text-xs font-semibold">
+                        {"Sambungkan sekarang"}
+                    </button>
+                }
+            </div>
+        }
+    }
+
+    /// A message from the server, as it is shown in `place`.
+    fn view_message(&self, ctx: &Context<Self>, m: &MessageData, place: Place) -> Html {
+        let editing = m.id.is_some() && self.editing == m.id && place != Place::Root;
+        let body = if m.deleted {
+            html! {
+                <div class="italic text-gray-400">{"🚫 Pesan ini telah dihapus"}</div>
+            }
+        } else if editing {
+            self.view_editor(ctx, &unlink_mentions(&m.message))
+        } else {
+            html! {
+                <>
+                    {view_text(&m.message, &self.state.username)}
+                    if let (Some(id), Some(_)) = (m.id, m.edited) {
+                        <button onclick={ctx.link().callback(move |_| Msg::ToggleHistory(id))} class="text-xs text-gray-400 hover:underline" title="Lihat riwayat suntingan">
+                            {"(diedit)"}
+                        </button>
+                    }
+                    if m.id.is_some() && self.history == m.id {
+                        <ol class="mt-2 space-y-1 border-l-2 border-gray-200 pl-3 text-xs text-gray-500">
+                            { for m.edits.iter().map(|revision| html! {
+                                <li class="flex gap-2">
+                                    <Timestamp time={revision.time as f64} now={self.now} />
+                                    <span class="break-words">{unlink_mentions(&revision.message)}</span>
+                                </li>
+                            }) }
+                        </ol>
+                    }
+                </>
+            }
+        };
+
+        let mut actions = vec![];
+        if let Some(id) = m.id {
+            if place == Place::Timeline {
+                let replies = self.state.replies(id);
+                let open = ctx
+                    .link()
+                    .callback(move |_| Msg::Action(Action::OpenThread(id)));
+                if replies > 0 {
+                    actions.push(html! {
+                        <button onclick={open.clone()} class="font-semibold hover:underline">
+                            {format!("💬 {} balasan", replies)}
+                        </button>
+                    });
+                }
+                actions.push(html! {
+                    <button onclick={open} class="hover:underline">{"↩ Balas di utas"}</button>
+                });
+            }
+            if self.state.own(id).is_some() && !editing && place != Place::Root {
+                actions.push(html! {
+                    <button onclick={ctx.link().callback(move |_| Msg::StartEdit(id))} class="hover:underline">
+                        {"✏ Ubah"}
+                    </button>
+                });
+                actions.push(html! {
+                    <button onclick={ctx.link().callback(move |_| Msg::Action(Action::Delete(id)))} class="text-red-500 hover:underline">
+                        {"🗑 Hapus"}
+                    </button>
+                });
+            }
+            if !m.deleted {
+                actions.push(html! {
+                    <div class="ml-auto hidden group-hover:flex gap-1" title="Beri reaksi">
+                        { for REACTIONS.iter().map(|emoji| {
+                            let react = ctx.link().callback(move |_| Msg::Action(Action::React(id, emoji.to_string())));
+                            html! {
+                                <button onclick={react} class="px-1 rounded hover:bg-indigo-50 text-base">{*emoji}</button>
+                            }
+                        }) }
+                    </div>
+                });
+            }
+        }
+        let footer = html! {
+            <>
+                {self.view_reactions(ctx, m)}
+                if !actions.is_empty() {
+                    <div class="mt-2 flex items-center gap-3 text-xs text-indigo-500">{ for actions }</div>
+                }
+            </>
+        };
+        let mentioned = !m.deleted && self.state.mentions_me(m);
+        self.view_bubble(&m.from, m.time as f64, mentioned, body, footer)
+    }
+
+    /// The avatars of those who have read up to a message.
+    fn view_readers(&self, readers: &[&str]) -> Html {
+        let title = format!("Dibaca oleh {}", readers.join(", "));
+        html! {
+            <div {title} class="flex justify-end items-center max-w-4xl pr-2 -mt-2">
+                { for readers.iter().take(MAX_READERS).map(|nick| html! {
+                    <img class="w-5 h-5 -ml-1 rounded-full border-2 border-white shadow-sm" src={self.state.avatar(nick)} alt={nick.to_string()}/>
+                }) }
+                if readers.len() > MAX_READERS {
+                    <span class="ml-1 text-xs text-gray-400">{format!("+{}", readers.len() - MAX_READERS)}</span>
+                }
+            </div>
+        }
+    }
+
+    /// The reactions under a message, one chip per emoji naming who picked
+    /// it; clicking a chip adds or takes back our own.
+    fn view_reactions(&self, ctx: &Context<Self>, m: &MessageData) -> Html {
+        let Some(id) = m.id else {
+            return html! {};
+        };
+        if m.reactions.is_empty() {
+            return html! {};
+        }
+        html! {
+            <div class="mt-2 flex flex-wrap gap-1">
+                { for m.reactions.iter().map(|(emoji, nicks)| {
+                    let ours = nicks.contains(&self.state.username);
+                    let class = if ours {
+                        "border-indigo-400 bg-indigo-50 text-indigo-700"
+                    } else {
+                        "border-gray-200 bg-gray-50 text-gray-600"
+                    };
+                    let emoji = emoji.clone();
+                    let title = nicks.join(", ");
+                    let label = format!("{} {}", emoji, reactors(nicks, &self.state.username));
+                    let toggle = ctx.link().callback(move |_| Msg::Action(Action::React(id, emoji.clone())));
+                    html! {
+                        <button onclick={toggle} {title} class={classes!("px-2", "py-0.5", "rounded-full", "border", "text-xs", class)}>
+                            {label}
+                        </button>
+                    }
+                }) }
+            </div>
+        }
+    }
+
+    /// The in-place editor for one of our messages.
+    fn view_editor(&self, ctx: &Context<Self>, message: &str) -> Html {
+        let save = ctx.link().callback(|e: FocusEvent| {
+            e.prevent_default();
+            Msg::SaveEdit
+        });
+        html! {
+            <form onsubmit={save} class="flex gap-2">
+                <input ref={self.edit_input.clone()} name="edit" value={message.to_owned()} class="grow py-1 px-3 bg-gray-50 border border-gray-200 rounded-lg outline-none focus:ring-2 focus:ring-indigo-500 text-gray-700" />
+                <button type="submit" class="px-3 rounded-lg bg-indigo-500 hover:bg-indigo-600 text-white text-xs font-semibold">
+                    {"Simpan"}
+                </button>
+                <button type="button" onclick={ctx.link().callback(|_| Msg::CancelEdit)} class="px-3 rounded-lg border border-gray-300 text-gray-600 hover:bg-gray-100 text-xs font-semibold">
+                    {"Batal"}
+                </button>
+            </form>
+        }
+    }
+
+    /// The open thread beside the conversation, if any.
+    fn view_thread(&self, ctx: &Context<Self>) -> Html {
+        let Some((parent, replies)) = self.state.open_thread() else {
+            return html! {};
+        };
+        let close = ctx.link().callback(|_| Msg::Action(Action::CloseThread));
+        let submit = ctx.link().callback(|e: FocusEvent| {
+            e.prevent_default();
+            Msg::SubmitReply
+        });
+        let conversation = self.state.conversation();
+        let pending = self
+            .state
+            .outbox
+            .iter()
+            .filter(|m| m.to == conversation && m.parent_id == parent.id);
+
+        html! {
+            <div class="flex-none w-96 h-screen flex flex-col bg-white border-l border-gray-200 shadow-lg">
+                <div class="h-16 flex items-center gap-2 px-4 border-b border-gray-200">
+                    <div class="text-lg font-bold text-gray-800">{"🧵 Utas"}</div>
+                    <div class="text-sm text-gray-500">{format!("{} balasan", replies.len())}</div>
+                    <button onclick={close} aria-label="Tutup utas" class="ml-auto px-2 text-gray-500 hover:text-gray-800">
+                        {"✕"}
+                    </button>
+                </div>
+                <div class="grow overflow-auto p-4 space-y-4 bg-gray-50">
+                    {self.view_message(ctx, parent, Place::Root)}
+                    <div class="border-t border-gray-200"></div>
+                    { for replies.into_iter().map(|m| self.view_message(ctx, m, Place::Reply)) }
+                    { for pending.map(|m| self.view_local(ctx, m)) }
+                </div>
+                <form onsubmit={submit} class="flex gap-2 p-4 border-t border-gray-200">
+                    <input ref={self.thread_input.clone()} oninput={self.typing_callback(ctx)} name="reply" placeholder="Balas di utas..." class="grow py-2 px-4 bg-gray-50 border border-gray-200 rounded-full outline-none focus:ring-2 focus:ring-indigo-500 text-gray-700" />
+                    <button type="submit" class="px-4 rounded-full bg-indigo-500 hover:bg-indigo-600 text-white text-sm font-semibold">
+                        {"Kirim"}
+                    </button>
+                </form>
+            </div>
+        }
+    }
+
+    fn view_local(&self, ctx: &Context<Self>, m: &LocalMessage) -> Html {
+        let status = match m.delivery {
+            Delivery::Pending => html! {
+                <span class="text-gray-400">{"⏳ Menunggu koneksi…"}</span>
+            },
+            Delivery::Written => html! {
+                <span class="text-gray-400">{"✓ Menunggu server…"}</span>
+            },
+            Delivery::Failed => {
+                let id = m.id;
+                let retry = ctx.link().callback(move |_| Msg::Action(Action::Retry(id)));
+                html! {
+                    <span class="text-red-500">
+                        {"⚠ Gagal terkirim · "}
+                        <button onclick={retry} class="underline hover:text-red-600">{"Coba lagi"}</button>
+                    </span>
+                }
+            }
+        };
+        self.view_bubble(
+            &self.state.username,
+            m.queued_at,
+            false,
+            view_text(&m.text, &self.state.username),
+            html! { <div class="mt-2 text-xs text-right">{status}</div> },
+        )
+    }
+
+    /// A message with the author's avatar; `mentioned` marks one that
+    /// mentions us.
+    fn view_bubble(
+        &self,
+        from: &str,
+        time: f64,
+        mentioned: bool,
+        body: Html,
+        footer: Html,
+    ) -> Html {
+        let avatar = self.state.avatar(from);
+        let card = if mentioned {
+            "bg-amber-50 border-amber-300"
+        } else {
+            "bg-white border-gray-100"
+        };
+        html! {
+            <div class="group flex items-start gap-3 max-w-4xl">
+                <img class="w-10 h-10 rounded-full border-2 border-indigo-200 shadow-sm" src={avatar} alt="avatar"/>
+                <div class={classes!("rounded-2xl", "rounded-tl-md", "shadow-md", "p-4", "border", "flex-grow", card)}>
+                    <div class="flex items-center gap-2 mb-2">
+                        <div class="text-sm font-semibold text-indigo-600">
+                            {from.to_owned()}
+                        </div>
+                        <Timestamp {time} now={self.now} />
+                    </div>
+                    <div class="text-gray-700">
+                        {body}
+                    </div>
+                    {footer}
+                </div>
             </div>
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::store::use_store;
+use crate::Route;
+
+/// Query string of [`Route::Login`].
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LoginQuery {
+    /// Path to continue to once the server has accepted the nickname.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    /// Set when the previous session ended because its token ran out.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub expired: bool,
+}
+
+impl LoginQuery {
+    /// Where to go after logging in: `next` if it names a page behind the
+    /// guard, otherwise the chat.
+    pub fn destination(&self) -> Route {
+        match self.next.as_deref().and_then(Route::recognize) {
+            Some(Route::Login | Route::Logout | Route::NotFound) | None => Route::Chat,
+            Some(route) => route,
+        }
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct RequireUserProps {
+    pub children: Children,
+}
+
+/// Renders its children only once the server has accepted a nickname;
+/// everyone else is sent to [`Route::Login`] with the page they asked for in
+/// `?next=`.
+#[function_component(RequireUser)]
+pub fn require_user(props: &RequireUserProps) -> Html {
+    let (_, app) = use_store();
+    let history = use_history().expect("RequireUser is rendered inside a router");
+    let registered = app.user.is_some();
+
+    use_effect_with_deps(
+        move |registered| {
+            if !*registered {
+                let query = LoginQuery {
+                    next: Some(history.location().pathname()),
+                    ..LoginQuery::default()
+                };
+                if let Err(e) = history.replace_with_query(Route::Login, query) {
+                    log::warn!("cannot redirect to login: {}", e);
+                }
+            }
+            || ()
+        },
+        registered,
+    );
+
+    if registered {
+        html! { <>{ for props.children.iter() }</> }
+    } else {
+        html! {}
+    }
+}
@@ -1,39 +1,239 @@
 use web_sys::HtmlInputElement;
 use yew::functional::*;
 use yew::prelude::*;
+use yew_agent::use_bridge;
 use yew_router::prelude::*;
+use yewchat_protocol::{validate_nick, AuthRequest, ServerMessage};
 
-use crate::Route;
-use crate::User;
+use crate::components::guard::LoginQuery;
+use crate::services::auth::authenticate;
+use crate::services::config;
+use crate::services::connection::Connection;
+use crate::services::event_bus::{Event, EventBus};
+use crate::services::session::{self, Session};
+use crate::services::websocket::ConnectionState;
+use crate::store::use_store;
+
+/// Shown when a session could not be resumed because its token ran out.
+const EXPIRED: &str = "Sesi telah berakhir, silakan masuk lagi";
 
 #[function_component(Login)]
 pub fn login() -> Html {
-    let username = use_state(|| String::new());
-    let user = use_context::<User>().expect("No context found.");
+    let saved = use_state(session::load);
+    let username = use_state(|| {
+        saved
+            .as_ref()
+            .map(|s| s.username.clone())
+            .unwrap_or_default()
+    });
+    let (store, app) = use_store();
+    // What is typed, which may not be a valid endpoint yet.
+    let server = use_state(|| app.settings.server.clone());
+    let secret = use_state(String::new);
+    // Waiting for a token from the auth endpoint.
+    let authenticating = use_state(|| false);
+    // The attempt we are waiting for the server to accept, if any.
+    let joining = use_state(|| None::<Session>);
+    let connection = use_context::<Connection>().expect("No context found.");
+    let history = use_history().expect("Login is rendered inside a router");
+    let query = history.location().query::<LoginQuery>().unwrap_or_default();
+    let rejected = use_state(|| query.expired.then(|| EXPIRED.to_string()));
+    let destination = query.destination();
+
+    {
+        let joining = joining.clone();
+        let rejected = rejected.clone();
+        let store = store.clone();
+        let connection = connection.clone();
+        use_bridge::<EventBus, _>(move |event| {
+            let attempt = match &*joining {
+                Some(attempt) => attempt.clone(),
+                None => return,
+            };
+            let refusal = match event {
+                Event::Frame(s) => match ServerMessage::decode(&s) {
+                    Ok(ServerMessage::RegisterAck(nick)) => {
+                        if store.get().settings.remember {
+                            session::save(&Session {
+                                username: nick.clone(),
+                                ..attempt.clone()
+                            });
+                        } else {
+                            session::clear();
+                        }
+                        joining.set(None);
+                        store.update(|state| {
+                            state.user = Some(nick);
+                            state.token = Some(attempt.token);
+                        });
+                        history.push(destination.clone());
+                        return;
+                    }
+                    Ok(ServerMessage::RegisterRejected(reason)) => reason,
+                    _ => return,
+                },
+                // The first attempt failed; rather than retrying in the
+                // background, let the user check the server address.
+                Event::Connection(status)
+                    if matches!(status.state, ConnectionState::Reconnecting { .. }) =>
+                {
+                    status
+                        .last_error
+                        .unwrap_or_else(|| "Tidak dapat terhubung ke server".into())
+                }
+                _ => return,
+            };
+            connection.disconnect();
+            joining.set(None);
+            rejected.set(Some(refusal));
+        });
+    }
+
+    let join = {
+        let joining = joining.clone();
+        let rejected = rejected.clone();
+        let store = store.clone();
+        Callback::from(move |attempt: Session| {
+            rejected.set(None);
+            store.update(|state| state.settings.server = attempt.server.clone());
+            connection.connect(
+                &config::with_token(&attempt.server, &attempt.token.token),
+                attempt.username.clone(),
+            );
+            joining.set(Some(attempt));
+        })
+    };
+
+    // A remembered session registers again straight away, as long as its
+    // token is still good.
+    {
+        let join = join.clone();
+        let rejected = rejected.clone();
+        use_effect_with_deps(
+            move |saved| {
+                match &**saved {
+                    Some(saved) if saved.expired(js_sys::Date::now()) => {
+                        session::clear();
+                        rejected.set(Some(EXPIRED.into()));
+                    }
+                    Some(saved) => join.emit(saved.clone()),
+                    None => {}
+                }
+                || ()
+            },
+            saved,
+        );
+    }
 
     let oninput = {
         let current_username = username.clone();
 
+        let rejected = rejected.clone();
         Callback::from(move |e: InputEvent| {
             let input: HtmlInputElement = e.target_unchecked_into();
             current_username.set(input.value());
+            rejected.set(None);
         })
     };
 
-    let onclick = {
+    let onsecretinput = {
+        let current_secret = secret.clone();
+
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            current_secret.set(input.value());
+        })
+    };
+
+    let onserverinput = {
+        let current_server = server.clone();
+
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            current_server.set(input.value());
+        })
+    };
+
+    let onremember = Callback::from(move |e: web_sys::Event| {
+        let input: HtmlInputElement = e.target_unchecked_into();
+        store.update(|state| state.settings.remember = input.checked());
+    });
+
+    let onsubmit = {
         let username = username.clone();
-        let user = user.clone();
-        Callback::from(move |_| *user.username.borrow_mut() = (*username).clone())
+        let secret = secret.clone();
+        let server = server.clone();
+        let authenticating = authenticating.clone();
+        let rejected = rejected.clone();
+        Callback::from(move |e: FocusEvent| {
+            e.prevent_default();
+            if let Err(e) = config::save_server(&server) {
+                log::warn!("{}", e);
+            }
+            let username = (*username).clone();
+            let server = config::normalize(&server).unwrap_or_else(config::server_url);
+            let request = AuthRequest {
+                nick: username.clone(),
+                secret: (*secret).clone(),
+            };
+            let join = join.clone();
+            let authenticating = authenticating.clone();
+            let rejected = rejected.clone();
+            rejected.set(None);
+            authenticating.set(true);
+            wasm_bindgen_futures::spawn_local(async move {
+                let result = authenticate(&server, &request).await;
+                authenticating.set(false);
+                match result {
+                    Ok(token) => join.emit(Session {
+                        username,
+                        server,
+                        token,
+                    }),
+                    Err(reason) => rejected.set(Some(reason)),
+                }
+            });
+        })
     };
 
+    let pending = *authenticating || joining.is_some();
+    let server_valid = config::normalize(&server).is_some();
+    // Checked here for quick feedback; the server has the final say.
+    let nick_error = validate_nick(&username)
+        .err()
+        .filter(|_| !username.is_empty());
+    let disabled = username.is_empty()
+        || nick_error.is_some()
+        || secret.is_empty()
+        || !server_valid
+        || pending;
+
     html! {
        <div class="bg-gray-800 flex w-screen">
             <div class="container mx-auto flex flex-col justify-center items-center">
-                <form class="m-4 flex">
-                    <input {oninput} class="rounded-l-lg p-4 border-t mr-0 border-b border-l text-gray-800 border-gray-200 bg-white" placeholder="Username" />
-                    <Link<Route> to={Route::Chat}> <button {onclick} disabled={username.len()<1} class="px-8 rounded-r-lg bg-violet-600	  text-white font-bold p-4 uppercase border-violet-600 border-t border-b border-r" >{"Go Chatting!"}</button></Link<Route>>
+                <form {onsubmit} class="m-4 flex">
+                    <input {oninput} value={(*username).clone()} readonly={pending} class="rounded-l-lg p-4 border-t mr-0 border-b border-l text-gray-800 border-gray-200 bg-white" placeholder="Username" />
+                    <input type="password" oninput={onsecretinput} value={(*secret).clone()} readonly={pending} class="p-4 border-t mr-0 border-b border-l text-gray-800 border-gray-200 bg-white" placeholder="Kata sandi atau kode undangan" />
+                    <button type="submit" {disabled} class="px-8 rounded-r-lg bg-violet-600	  text-white font-bold p-4 uppercase border-violet-600 border-t border-b border-r" >{if pending { "Menunggu server…" } else { "Go Chatting!" }}</button>
                 </form>
+                if let Some(error) = nick_error {
+                    <div class="mb-2 text-sm text-red-400">{error.to_string()}</div>
+                }
+                if let Some(reason) = &*rejected {
+                    <div class="mb-2 text-sm text-red-400">{reason.clone()}</div>
+                }
+                <label class="flex items-center gap-2 text-sm text-gray-400">
+                    {"Server"}
+                    <input oninput={onserverinput} value={(*server).clone()} class="rounded-lg px-3 py-1 text-gray-800 bg-white w-72" placeholder={config::DEFAULT_SERVER} />
+                </label>
+                if !server_valid {
+                    <div class="mt-2 text-sm text-red-400">{"Alamat server harus diawali ws:// atau wss://"}</div>
+                }
+                <label class="mt-2 flex items-center gap-2 text-sm text-gray-400">
+                    <input type="checkbox" checked={app.settings.remember} onchange={onremember} />
+                    {"Ingat saya di perangkat ini"}
+                </label>
             </div>
         </div>
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,32 @@
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::services::connection::Connection;
+use crate::services::session;
+use crate::store::Store;
+use crate::Route;
+
+/// Ends the session: forgets the remembered identity, closes the socket and
+/// goes back to [`Route::Login`].
+#[function_component(Logout)]
+pub fn logout() -> Html {
+    let store = use_context::<Store>().expect("No context found.");
+    let connection = use_context::<Connection>().expect("No context found.");
+    let history = use_history().expect("Logout is rendered inside a router");
+
+    use_effect_with_deps(
+        move |_| {
+            session::clear();
+            connection.disconnect();
+            store.update(|state| {
+                state.user = None;
+                state.token = None;
+            });
+            history.replace(Route::Login);
+            || ()
+        },
+        (),
+    );
+
+    html! {}
+}
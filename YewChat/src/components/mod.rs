@@ -1,2 +1,5 @@
 pub mod chat;
-pub mod login;
\ No newline at end of file
+pub mod guard;
+pub mod login;
+pub mod logout;
+pub mod timestamp;
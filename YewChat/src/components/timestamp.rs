@@ -0,0 +1,78 @@
+use js_sys::{Date, Object, Reflect};
+use wasm_bindgen::JsValue;
+use yew::prelude::*;
+
+const MINUTE: f64 = 60_000.0;
+const HOUR: f64 = 60.0 * MINUTE;
+const DAY: f64 = 24.0 * HOUR;
+
+const LOCALE: &str = "id-ID";
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    /// When the message was sent, in milliseconds since the epoch.
+    pub time: f64,
+    /// The current time, so every timestamp on screen ages together.
+    pub now: f64,
+}
+
+/// Relative send time ("2 menit lalu") with the full local date and time on hover.
+#[function_component(Timestamp)]
+pub fn timestamp(props: &Props) -> Html {
+    let absolute: String = Date::new(&JsValue::from_f64(props.time))
+        .to_locale_string(LOCALE, &JsValue::UNDEFINED)
+        .into();
+
+    html! {
+        <div class="text-xs text-gray-400" title={absolute}>
+            {relative(props.now - props.time)}
+        </div>
+    }
+}
+
+fn relative(elapsed: f64) -> String {
+    // Clocks between client and server drift a little; a message from the
+    // "future" is still one that just arrived.
+    let elapsed = elapsed.max(0.0);
+    if elapsed < MINUTE {
+        "baru saja".into()
+    } else if elapsed < HOUR {
+        format!("{} menit lalu", (elapsed / MINUTE) as u64)
+    } else if elapsed < DAY {
+        format!("{} jam lalu", (elapsed / HOUR) as u64)
+    } else if elapsed < 2.0 * DAY {
+        "kemarin".into()
+    } else {
+        format!("{} hari lalu", (elapsed / DAY) as u64)
+    }
+}
+
+/// Identifies the local calendar day `time` falls on.
+pub fn day_key(time: f64) -> (u32, u32, u32) {
+    let date = Date::new(&JsValue::from_f64(time));
+    (date.get_full_year(), date.get_month(), date.get_date())
+}
+
+/// Heading for the separator above the first message of a day.
+pub fn day_label(time: f64, now: f64) -> String {
+    let day = day_key(time);
+    if day == day_key(now) {
+        return "Hari ini".into();
+    }
+    if day == day_key(now - DAY) {
+        return "Kemarin".into();
+    }
+
+    let options = Object::new();
+    for (key, value) in [
+        ("weekday", "long"),
+        ("day", "numeric"),
+        ("month", "long"),
+        ("year", "numeric"),
+    ] {
+        let _ = Reflect::set(&options, &key.into(), &value.into());
+    }
+    Date::new(&JsValue::from_f64(time))
+        .to_locale_date_string(LOCALE, &options)
+        .into()
+}
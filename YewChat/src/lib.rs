@@ -1,10 +1,11 @@
 #![recursion_limit = "512"]
+// `html!` in yew 0.19 expands to bindings and expressions that newer clippy flags.
+#![allow(clippy::let_unit_value, clippy::unnecessary_operation)]
 
-mod components;
-mod services;
-
-use std::cell::RefCell;
-use std::rc::Rc;
+pub mod components;
+pub mod services;
+pub mod state;
+pub mod store;
 
 use wasm_bindgen::prelude::*;
 use yew::functional::*;
@@ -12,7 +13,12 @@ use yew::prelude::*;
 use yew_router::prelude::*;
 
 use components::chat::Chat;
+use components::guard::RequireUser;
 use components::login::Login;
+use components::logout::Logout;
+use services::config;
+use services::connection::Connection;
+use store::{use_connection_feed, AppState, Settings, Store};
 
 // When the `wee_alloc` feature is enabled, this uses `wee_alloc` as the global
 // allocator.
@@ -22,47 +28,55 @@ use components::login::Login;
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
-#[derive(Debug, Clone, Copy, PartialEq, Routable)]
+#[derive(Debug, Clone, PartialEq, Routable)]
 pub enum Route {
     #[at("/")]
     Login,
+    /// The lobby.
     #[at("/chat")]
     Chat,
+    #[at("/room/:id")]
+    Room { id: String },
+    #[at("/dm/:nick")]
+    Direct { nick: String },
+    #[at("/logout")]
+    Logout,
     #[not_found]
     #[at("/404")]
     NotFound,
 }
 
-pub type User = Rc<UserInner>;
-
-#[derive(Debug, PartialEq)]
-pub struct UserInner {
-    pub username: RefCell<String>,
-}
-
 #[function_component(Main)]
 fn main() -> Html {
-    let ctx = use_state(|| {
-        Rc::new(UserInner {
-            username: RefCell::new("initial".into()),
-        })
+    let store = use_state(|| {
+        Store::new(AppState::new(Settings {
+            server: config::server_url(),
+            remember: true,
+        }))
     });
+    let connection = use_state(Connection::default);
+    use_connection_feed(&store);
 
     html! {
-        <ContextProvider<User> context={(*ctx).clone()}>
-            <BrowserRouter>
-                <div class="flex w-screen h-screen">
-                    <Switch<Route> render={Switch::render(switch)}/>
-                </div>
-            </BrowserRouter>
-        </ContextProvider<User>>
+        <ContextProvider<Store> context={(*store).clone()}>
+            <ContextProvider<Connection> context={(*connection).clone()}>
+                <BrowserRouter>
+                    <div class="flex w-screen h-screen">
+                        <Switch<Route> render={Switch::render(switch)}/>
+                    </div>
+                </BrowserRouter>
+            </ContextProvider<Connection>>
+        </ContextProvider<Store>>
     }
 }
 
 fn switch(selected_route: &Route) -> Html {
     match selected_route {
         Route::Login => html! {<Login />},
-        Route::Chat => html! {<Chat/>},
+        Route::Chat => html! {<RequireUser><Chat/></RequireUser>},
+        Route::Room { id } => html! {<RequireUser><Chat room={id.clone()}/></RequireUser>},
+        Route::Direct { nick } => html! {<RequireUser><Chat direct={nick.clone()}/></RequireUser>},
+        Route::Logout => html! {<Logout />},
         Route::NotFound => html! {<h1>{"404 baby"}</h1>},
     }
 }
@@ -0,0 +1,32 @@
+use reqwasm::http::Request;
+use yewchat_protocol::{AuthError, AuthRequest, AuthToken};
+
+use crate::services::config;
+
+/// Trades `request` for a token at the auth endpoint of `server`. Errors are
+/// written for users.
+pub async fn authenticate(server: &str, request: &AuthRequest) -> Result<AuthToken, String> {
+    let url =
+        config::auth_url(server).ok_or_else(|| format!("alamat server tidak valid: {}", server))?;
+    let body = serde_json::to_string(request).expect("request always serializes");
+    let response = Request::post(&url)
+        .header("Content-Type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| {
+            log::warn!("auth request failed: {}", e);
+            "Tidak dapat terhubung ke server".to_string()
+        })?;
+
+    if response.ok() {
+        return response
+            .json::<AuthToken>()
+            .await
+            .map_err(|e| format!("Balasan server tidak dikenali: {}", e));
+    }
+    match response.json::<AuthError>().await {
+        Ok(e) => Err(e.error),
+        Err(_) => Err(format!("Server menolak masuk ({})", response.status())),
+    }
+}
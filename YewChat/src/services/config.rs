@@ -0,0 +1,108 @@
+use gloo_storage::{LocalStorage, Storage};
+use web_sys::UrlSearchParams;
+use yewchat_protocol::{AUTH_PATH, TOKEN_PARAM};
+
+/// Endpoint used when nothing else is configured. Override at build time with
+/// `YEWCHAT_SERVER=wss://chat.example.com npm run build`.
+pub const DEFAULT_SERVER: &str = match option_env!("YEWCHAT_SERVER") {
+    Some(url) => url,
+    None => "ws://127.0.0.1:8080",
+};
+
+/// `?server=` query parameter that overrides every other source.
+const QUERY_PARAM: &str = "server";
+/// `<meta name="yewchat-server" content="...">` set by whoever deploys `static/index.html`.
+const META_NAME: &str = "yewchat-server";
+/// localStorage key for the endpoint typed into the login screen.
+const STORAGE_KEY: &str = "yewchat.server";
+
+/// Resolves the WebSocket endpoint at runtime.
+///
+/// Sources are tried in order: query parameter, saved setting, `<meta>` tag,
+/// then [`DEFAULT_SERVER`]. Values that do not normalize are skipped.
+pub fn server_url() -> String {
+    [query_param(), saved_server(), meta_tag()]
+        .into_iter()
+        .flatten()
+        .find_map(|url| normalize(&url))
+        .unwrap_or_else(|| DEFAULT_SERVER.to_owned())
+}
+
+/// Remembers `url` as the preferred endpoint, or forgets it when it is the
+/// one that would be used anyway.
+pub fn save_server(url: &str) -> Result<(), String> {
+    let url = normalize(url).ok_or_else(|| format!("alamat server tidak valid: {}", url))?;
+    if url == DEFAULT_SERVER {
+        LocalStorage::delete(STORAGE_KEY);
+        return Ok(());
+    }
+    LocalStorage::set(STORAGE_KEY, url).map_err(|e| e.to_string())
+}
+
+/// Turns what a person might type into a WebSocket URL: `ws://` and `wss://`
+/// pass through, `http(s)://` maps to `ws(s)://`, and a bare `host:port`
+/// gets the scheme matching the page so https deployments get `wss://`.
+pub fn normalize(url: &str) -> Option<String> {
+    let url = url.trim();
+    if url.is_empty() {
+        return None;
+    }
+    if url.starts_with("ws://") || url.starts_with("wss://") {
+        return Some(url.to_owned());
+    }
+    if let Some(rest) = url.strip_prefix("http://") {
+        return Some(format!("ws://{}", rest));
+    }
+    if let Some(rest) = url.strip_prefix("https://") {
+        return Some(format!("wss://{}", rest));
+    }
+    if url.contains("://") {
+        return None;
+    }
+    let scheme = if page_is_secure() { "wss" } else { "ws" };
+    Some(format!("{}://{}", scheme, url))
+}
+
+/// The HTTP auth endpoint served alongside the WebSocket at `server`, a
+/// normalized `ws://` or `wss://` URL.
+pub fn auth_url(server: &str) -> Option<String> {
+    let (scheme, rest) = match server.split_once("://")? {
+        ("ws", rest) => ("http", rest),
+        ("wss", rest) => ("https", rest),
+        _ => return None,
+    };
+    let host = rest.split(['/', '?', '#']).next()?;
+    Some(format!("{}://{}{}", scheme, host, AUTH_PATH))
+}
+
+/// `server` with `token` attached the way the handshake expects. Tokens are
+/// URL-safe, so no escaping is needed.
+pub fn with_token(server: &str, token: &str) -> String {
+    let separator = if server.contains('?') { '&' } else { '?' };
+    format!("{}{}{}={}", server, separator, TOKEN_PARAM, token)
+}
+
+fn query_param() -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    UrlSearchParams::new_with_str(&search)
+        .ok()?
+        .get(QUERY_PARAM)
+}
+
+fn saved_server() -> Option<String> {
+    LocalStorage::get(STORAGE_KEY).ok()
+}
+
+fn meta_tag() -> Option<String> {
+    web_sys::window()?
+        .document()?
+        .query_selector(&format!("meta[name=\"{}\"]", META_NAME))
+        .ok()??
+        .get_attribute("content")
+}
+
+fn page_is_secure() -> bool {
+    web_sys::window()
+        .and_then(|w| w.location().protocol().ok())
+        .is_some_and(|p| p == "https:")
+}
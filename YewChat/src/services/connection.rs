@@ -0,0 +1,68 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use yewchat_protocol::ClientMessage;
+
+use crate::services::outbox::Outgoing;
+use crate::services::transport::Transport;
+use crate::services::websocket::{ConnectionStatus, WebsocketService};
+
+/// The app-wide connection, owned by `Main` and handed to components through
+/// a `ContextProvider`.
+///
+/// Login connects, logout disconnects; components in between only send
+/// through it and listen on the [`EventBus`](super::event_bus::EventBus).
+#[derive(Clone, Default)]
+pub struct Connection {
+    transport: Rc<RefCell<Option<Rc<dyn Transport>>>>,
+}
+
+impl PartialEq for Connection {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.transport, &other.transport)
+    }
+}
+
+impl Connection {
+    /// Opens a WebSocket for `username`, replacing (and closing) any previous
+    /// connection.
+    pub fn connect(&self, url: &str, username: String) {
+        self.install(Rc::new(WebsocketService::new(url, username)));
+    }
+
+    /// Uses `transport` from now on, closing any previous one. This is how
+    /// tests plug in a `LoopbackTransport`.
+    pub fn install(&self, transport: Rc<dyn Transport>) {
+        if let Some(previous) = self.transport.borrow_mut().replace(transport) {
+            previous.close();
+        }
+    }
+
+    pub fn disconnect(&self) {
+        if let Some(previous) = self.transport.borrow_mut().take() {
+            previous.close();
+        }
+    }
+
+    pub fn status(&self) -> ConnectionStatus {
+        self.transport
+            .borrow()
+            .as_ref()
+            .map(|t| t.status())
+            .unwrap_or_default()
+    }
+
+    /// Queues `msg`; hands it back when there is no connection to queue on.
+    pub fn send(&self, msg: &ClientMessage) -> Result<(), Outgoing> {
+        match self.transport.borrow().as_ref() {
+            Some(transport) => transport.send(msg),
+            None => Err(Outgoing::new(msg)),
+        }
+    }
+
+    pub fn reconnect_now(&self) {
+        if let Some(transport) = self.transport.borrow().as_ref() {
+            transport.reconnect_now();
+        }
+    }
+}
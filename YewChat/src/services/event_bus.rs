@@ -1,47 +1,89 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use yew_agent::{Agent, AgentLink, Context, HandlerId};
+use yewchat_protocol::ServerMessage;
+
+use crate::services::outbox::OutboxEvent;
+use crate::services::websocket::{ConnectionState, ConnectionStatus};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Request {
     EventBusMsg(String),
+    Connection(ConnectionStatus),
+    Outbox(OutboxEvent),
+}
+
+/// What subscribers of the bus receive.
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// A raw frame from the server.
+    Frame(String),
+    /// The socket changed state.
+    Connection(ConnectionStatus),
+    /// A queued frame moved through the outbox.
+    Outbox(OutboxEvent),
 }
 
 pub struct EventBus {
     link: AgentLink<EventBus>,
     subscribers: HashSet<HandlerId>,
+    /// The latest `users` frame, replayed to whoever subscribes later. The
+    /// server only sends the list when it changes, and `Chat` mounts after
+    /// `Login` has already seen the one that followed its registration.
+    roster: Option<String>,
 }
 
 impl Agent for EventBus {
     type Reach = Context<Self>;
     type Message = ();
     type Input = Request;
-    type Output = String;
+    type Output = Event;
 
     fn create(link: AgentLink<Self>) -> Self {
         Self {
             link,
             subscribers: HashSet::new(),
+            roster: None,
         }
     }
 
     fn update(&mut self, _msg: Self::Message) {}
 
     fn handle_input(&mut self, msg: Self::Input, _id: HandlerId) {
-        match msg {
+        let event = match msg {
             Request::EventBusMsg(s) => {
-                for sub in self.subscribers.iter() {
-                    self.link.respond(*sub, s.clone())
+                if let Ok(ServerMessage::Users(_)) = ServerMessage::decode(&s) {
+                    self.roster = Some(s.clone());
+                }
+                Event::Frame(s)
+            }
+            Request::Connection(status) => {
+                if matches!(
+                    status.state,
+                    ConnectionState::Closed | ConnectionState::Expired
+                ) {
+                    self.roster = None;
                 }
+                Event::Connection(status)
             }
+            Request::Outbox(event) => Event::Outbox(event),
+        };
+        for sub in self.subscribers.iter() {
+            self.link.respond(*sub, event.clone())
         }
     }
 
     fn connected(&mut self, id: HandlerId) {
         self.subscribers.insert(id);
+        if !id.is_respondable() {
+            return;
+        }
+        if let Some(roster) = &self.roster {
+            self.link.respond(id, Event::Frame(roster.clone()));
+        }
     }
 
     fn disconnected(&mut self, id: HandlerId) {
         self.subscribers.remove(&id);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,172 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use yew_agent::{Dispatched, Dispatcher};
+use yewchat_protocol::{
+    ClientMessage, MembersData, MessageData, PostData, ReactionData, ReadData, ServerMessage, LOBBY,
+};
+
+use crate::services::event_bus::{EventBus, Request};
+use crate::services::outbox::{Delivery, OutboxEvent, Outgoing};
+use crate::services::transport::Transport;
+use crate::services::websocket::{ConnectionState, ConnectionStatus};
+
+/// An in-memory [`Transport`] that stands in for a server.
+///
+/// It starts out open, accepts every `register`, echoes posts and direct
+/// messages back from `username` with increasing ids, lets it edit, delete
+/// and react to them and lets it into any room the way a server would, and lets
+/// tests script any other frame with [`deliver`].
+///
+/// [`deliver`]: LoopbackTransport::deliver
+pub struct LoopbackTransport {
+    event_bus: RefCell<Dispatcher<EventBus>>,
+    status: RefCell<ConnectionStatus>,
+    username: String,
+    sent: RefCell<Vec<ClientMessage>>,
+    /// Every message echoed so far, as last edited.
+    echoed: RefCell<Vec<MessageData>>,
+}
+
+impl LoopbackTransport {
+    pub fn new(username: &str) -> Self {
+        Self {
+            event_bus: RefCell::new(EventBus::dispatcher()),
+            status: RefCell::new(ConnectionStatus {
+                state: ConnectionState::Open,
+                last_error: None,
+            }),
+            username: username.to_owned(),
+            sent: RefCell::new(vec![]),
+            echoed: RefCell::new(vec![]),
+        }
+    }
+
+    /// Publishes a raw frame as if the server had sent it.
+    pub fn deliver(&self, frame: &str) {
+        self.publish(Request::EventBusMsg(frame.to_owned()));
+    }
+
+    /// Publishes a connection state change.
+    pub fn set_status(&self, status: ConnectionStatus) {
+        *self.status.borrow_mut() = status.clone();
+        self.publish(Request::Connection(status));
+    }
+
+    /// Everything sent through this transport so far.
+    pub fn sent(&self) -> Vec<ClientMessage> {
+        self.sent.borrow().clone()
+    }
+
+    fn publish(&self, request: Request) {
+        self.event_bus.borrow_mut().send(request);
+    }
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&self, msg: &ClientMessage) -> Result<(), Outgoing> {
+        let entry = Outgoing::new(msg);
+        if self.status.borrow().state != ConnectionState::Open {
+            return Err(entry);
+        }
+        self.sent.borrow_mut().push(msg.clone());
+        self.publish(Request::Outbox(OutboxEvent::Queued(entry.clone())));
+        self.publish(Request::Outbox(OutboxEvent::Delivery(
+            entry.id,
+            Delivery::Written,
+        )));
+
+        let now = js_sys::Date::now() as u64;
+        let echo = |post: PostData, to: Option<String>| {
+            let mut echoed = self.echoed.borrow_mut();
+            let data = MessageData {
+                id: Some(echoed.len() as u64 + 1),
+                room: post.room,
+                to,
+                parent_id: post.parent_id,
+                from: self.username.clone(),
+                message: post.message,
+                time: now,
+                edited: None,
+                edits: vec![],
+                deleted: false,
+                reactions: BTreeMap::new(),
+            };
+            echoed.push(data.clone());
+            data
+        };
+        let reply = match msg.clone() {
+            ClientMessage::Register(nick) => ServerMessage::RegisterAck(nick),
+            ClientMessage::Resume(_) => ServerMessage::RegisterAck(self.username.clone()),
+            ClientMessage::Message(message) => ServerMessage::Message(echo(
+                PostData {
+                    room: LOBBY.into(),
+                    message,
+                    parent_id: None,
+                },
+                None,
+            )),
+            ClientMessage::Post(post) => ServerMessage::Message(echo(post, None)),
+            ClientMessage::Direct(direct) => ServerMessage::Direct(echo(
+                PostData {
+                    room: LOBBY.into(),
+                    message: direct.message,
+                    parent_id: direct.parent_id,
+                },
+                Some(direct.to),
+            )),
+            ClientMessage::Edit(edit) => {
+                let mut echoed = self.echoed.borrow_mut();
+                let Some(data) = echoed
+                    .iter_mut()
+                    .find(|m| m.id == Some(edit.id) && !m.deleted)
+                else {
+                    return Ok(());
+                };
+                data.edit(edit.message, now);
+                ServerMessage::Edit(data.clone())
+            }
+            ClientMessage::Delete(id) => {
+                let mut echoed = self.echoed.borrow_mut();
+                let Some(data) = echoed.iter_mut().find(|m| m.id == Some(id) && !m.deleted) else {
+                    return Ok(());
+                };
+                data.delete();
+                ServerMessage::Delete(id)
+            }
+            ClientMessage::React(reaction) => ServerMessage::React(ReactionData {
+                from: Some(self.username.clone()),
+                ..reaction
+            }),
+            ClientMessage::Unreact(reaction) => ServerMessage::Unreact(ReactionData {
+                from: Some(self.username.clone()),
+                ..reaction
+            }),
+            ClientMessage::Read(read) => ServerMessage::Read(ReadData {
+                from: Some(self.username.clone()),
+                ..read
+            }),
+            ClientMessage::Join(room) => ServerMessage::Members(MembersData {
+                room,
+                users: vec![self.username.clone()],
+            }),
+            ClientMessage::ListRooms => ServerMessage::Rooms(vec![LOBBY.into()]),
+            ClientMessage::Leave(_) | ClientMessage::Typing(_) => return Ok(()),
+        };
+        self.deliver(&reply.encode());
+        Ok(())
+    }
+
+    fn close(&self) {
+        self.set_status(ConnectionStatus {
+            state: ConnectionState::Closed,
+            last_error: None,
+        });
+    }
+
+    fn reconnect_now(&self) {}
+
+    fn status(&self) -> ConnectionStatus {
+        self.status.borrow().clone()
+    }
+}
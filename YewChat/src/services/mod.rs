@@ -1,2 +1,10 @@
+pub mod auth;
+pub mod config;
+pub mod connection;
+#[cfg(feature = "test-support")]
+pub mod loopback;
+pub mod outbox;
+pub mod session;
+pub mod transport;
 pub mod websocket;
-pub mod event_bus;
\ No newline at end of file
+pub mod event_bus;
@@ -0,0 +1,153 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+
+use gloo_storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+use yewchat_protocol::{ClientMessage, DirectData, PostData, LOBBY};
+
+/// Frames queued longer than this are given up on rather than flushed.
+const MAX_AGE_MS: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+/// Frames beyond this many are refused instead of queued.
+const CAPACITY: usize = 500;
+
+thread_local! {
+    static NEXT_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+/// A frame waiting for the socket.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Outgoing {
+    /// Local id, unique across reloads, used to report delivery.
+    pub id: u64,
+    pub frame: String,
+    /// When the frame was queued, in milliseconds since the epoch.
+    pub queued_at: f64,
+}
+
+impl Outgoing {
+    pub fn new(msg: &ClientMessage) -> Self {
+        let queued_at = js_sys::Date::now();
+        // Milliseconds keep ids unique across reloads, the counter within one.
+        let seq = NEXT_ID.with(|n| {
+            let seq = n.get();
+            n.set(seq.wrapping_add(1));
+            seq
+        });
+        Self {
+            id: (queued_at as u64) * 1000 + seq % 1000,
+            frame: msg.encode(),
+            queued_at,
+        }
+    }
+
+    /// The chat line this frame carries, if it is one.
+    pub fn post(&self) -> Option<PostData> {
+        match ClientMessage::decode(&self.frame) {
+            Ok(ClientMessage::Post(post)) => Some(post),
+            Ok(ClientMessage::Message(message)) => Some(PostData {
+                room: LOBBY.into(),
+                message,
+                parent_id: None,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Whether the frame only means something at the moment it is sent, like
+    /// a typing notice or a read marker, so it is never queued for later.
+    pub fn is_ephemeral(&self) -> bool {
+        matches!(
+            ClientMessage::decode(&self.frame),
+            Ok(ClientMessage::Typing(_) | ClientMessage::Read(_))
+        )
+    }
+
+    /// The direct message this frame carries, if it is one.
+    pub fn direct(&self) -> Option<DirectData> {
+        match ClientMessage::decode(&self.frame) {
+            Ok(ClientMessage::Direct(direct)) => Some(direct),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Delivery {
+    /// Waiting for the socket.
+    Pending,
+    /// Written to the socket. Only the server's echo tells it arrived,
+    /// which takes the line out of the outbox.
+    Written,
+    /// Dropped without being sent.
+    Failed,
+}
+
+/// Published on the [`EventBus`](super::event_bus::EventBus) as frames move
+/// through the queue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum OutboxEvent {
+    Queued(Outgoing),
+    Delivery(u64, Delivery),
+}
+
+/// Unsent frames, mirrored to localStorage so they survive a reload.
+pub struct Outbox {
+    key: String,
+    entries: VecDeque<Outgoing>,
+}
+
+impl Outbox {
+    /// Restores the queue saved for `username`, splitting off entries too old
+    /// to still be worth sending.
+    pub fn load(username: &str) -> (Self, Vec<Outgoing>) {
+        let key = format!("yewchat.outbox.{}", username);
+        let saved: Vec<Outgoing> = LocalStorage::get(&key).unwrap_or_default();
+        let now = js_sys::Date::now();
+        let (entries, expired): (Vec<_>, Vec<_>) = saved
+            .into_iter()
+            .partition(|e| now - e.queued_at < MAX_AGE_MS);
+
+        let outbox = Self {
+            key,
+            entries: entries.into(),
+        };
+        outbox.save();
+        (outbox, expired)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Outgoing> {
+        self.entries.iter()
+    }
+
+    pub fn front(&self) -> Option<&Outgoing> {
+        self.entries.front()
+    }
+
+    /// Queues `entry`, handing it back if the queue is full.
+    pub fn push(&mut self, entry: Outgoing) -> Result<(), Outgoing> {
+        if self.entries.len() >= CAPACITY {
+            return Err(entry);
+        }
+        self.entries.push_back(entry);
+        self.save();
+        Ok(())
+    }
+
+    pub fn pop_front(&mut self) -> Option<Outgoing> {
+        let entry = self.entries.pop_front();
+        self.save();
+        entry
+    }
+
+    fn save(&self) {
+        let result = if self.entries.is_empty() {
+            LocalStorage::delete(&self.key);
+            Ok(())
+        } else {
+            LocalStorage::set(&self.key, &self.entries)
+        };
+        if let Err(e) = result {
+            log::warn!("cannot persist outbox: {}", e);
+        }
+    }
+}
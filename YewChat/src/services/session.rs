@@ -0,0 +1,38 @@
+use gloo_storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+use yewchat_protocol::AuthToken;
+
+/// localStorage key for the remembered identity.
+const STORAGE_KEY: &str = "yewchat.session";
+
+/// Who we were last time, so a reload can register again without asking.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub username: String,
+    /// The normalized endpoint the nickname was accepted on.
+    pub server: String,
+    pub token: AuthToken,
+}
+
+impl Session {
+    /// Whether the token has run out by `now`, in milliseconds since the
+    /// epoch.
+    pub fn expired(&self, now: f64) -> bool {
+        self.token.expires_at as f64 <= now
+    }
+}
+
+/// The remembered session, if any.
+pub fn load() -> Option<Session> {
+    LocalStorage::get(STORAGE_KEY).ok()
+}
+
+pub fn save(session: &Session) {
+    if let Err(e) = LocalStorage::set(STORAGE_KEY, session) {
+        log::warn!("cannot remember session: {}", e);
+    }
+}
+
+pub fn clear() {
+    LocalStorage::delete(STORAGE_KEY);
+}
@@ -0,0 +1,23 @@
+use yewchat_protocol::ClientMessage;
+
+use crate::services::outbox::Outgoing;
+use crate::services::websocket::ConnectionStatus;
+
+/// A connection to a chat server.
+///
+/// Sending goes through the trait; receiving does not: implementations
+/// publish inbound frames, [`ConnectionStatus`] changes and [`OutboxEvent`]s
+/// on the [`EventBus`], which is where components listen.
+///
+/// [`OutboxEvent`]: crate::services::outbox::OutboxEvent
+/// [`EventBus`]: crate::services::event_bus::EventBus
+pub trait Transport {
+    /// Queues `msg` for the server, handing it back if it cannot be queued.
+    fn send(&self, msg: &ClientMessage) -> Result<(), Outgoing>;
+    /// Closes the connection for good.
+    fn close(&self);
+    /// Skips any pending reconnect delay.
+    fn reconnect_now(&self);
+    /// The current state of the connection.
+    fn status(&self) -> ConnectionStatus;
+}
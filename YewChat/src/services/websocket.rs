@@ -1,53 +1,412 @@
-use futures::{channel::mpsc::Sender, SinkExt, StreamExt};
-use reqwasm::websocket::{futures::WebSocket, Message};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use futures::channel::mpsc::{Receiver, Sender, UnboundedReceiver, UnboundedSender};
+use futures::future::{Fuse, FusedFuture};
+use futures::{pin_mut, select, FutureExt, SinkExt, StreamExt};
+use gloo_timers::future::TimeoutFuture;
+use reqwasm::websocket::{futures::WebSocket, Message, WebSocketError};
+use serde::{Deserialize, Serialize};
 
 use wasm_bindgen_futures::spawn_local;
-use yew_agent::Dispatched;
+use yew_agent::{Dispatched, Dispatcher};
+use yewchat_protocol::{
+    ClientMessage, ResumeData, ServerMessage, CLOSE_TOKEN_EXPIRED, CLOSE_TOKEN_REJECTED,
+};
 
 use crate::services::event_bus::{EventBus, Request};
+use crate::services::outbox::{Delivery, Outbox, OutboxEvent, Outgoing};
+use crate::services::transport::Transport;
+
+/// First retry delay after a connection drops.
+const BACKOFF_INITIAL_MS: u32 = 500;
+/// Upper bound for the retry delay.
+const BACKOFF_MAX_MS: u32 = 30_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConnectionState {
+    /// Waiting for the socket to open and the server to accept our
+    /// `register` or `resume`.
+    Connecting,
+    /// Registered and exchanging frames.
+    Open,
+    /// Lost the socket; the next attempt starts after `delay_ms`.
+    Reconnecting { attempt: u32, delay_ms: u32 },
+    /// The service has shut down and will not reconnect.
+    Closed,
+    /// The server ended the session because its token ran out or is no
+    /// longer accepted; reconnecting with it would be refused, so the service
+    /// has stopped.
+    Expired,
+}
+
+/// Published on the [`EventBus`] whenever the connection changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionStatus {
+    pub state: ConnectionState,
+    /// The most recent failure, kept until the next one replaces it.
+    pub last_error: Option<String>,
+}
+
+impl Default for ConnectionStatus {
+    fn default() -> Self {
+        Self {
+            state: ConnectionState::Closed,
+            last_error: None,
+        }
+    }
+}
 
+/// Out-of-band requests from the handle to its supervisor.
+enum Control {
+    ReconnectNow,
+    Close,
+}
+
+/// The [`Transport`] backed by a real `reqwasm` WebSocket.
 pub struct WebsocketService {
-    pub tx: Sender<String>,
+    tx: Sender<Outgoing>,
+    control: UnboundedSender<Control>,
+    status: Rc<RefCell<ConnectionStatus>>,
 }
 
 impl WebsocketService {
-    pub fn new() -> Self {
-        let ws = WebSocket::open("ws://127.0.0.1:8080").unwrap();
+    /// Connects to `url` as `username` and keeps the connection alive until
+    /// the service is closed or dropped.
+    ///
+    /// The first connection starts with a `register` frame for `username`.
+    /// Reconnections present the resume token the server handed out instead,
+    /// so the same identity is restored and the messages since the last one
+    /// seen are replayed. A close with [`CLOSE_TOKEN_EXPIRED`] or
+    /// [`CLOSE_TOKEN_REJECTED`] ends the service as
+    /// [`ConnectionState::Expired`] instead, since the token in `url` will not
+    /// be accepted again.
+    /// Frames sent while the socket is down are kept in an [`Outbox`] saved
+    /// per user, and delivered in order once it is back and the server has
+    /// answered the greeting with `registerAck`, even after a reload.
+    /// [Ephemeral](Outgoing::is_ephemeral) frames are only sent while the
+    /// connection is open, and dropped otherwise.
+    pub fn new(url: &str, username: String) -> Self {
+        let (in_tx, in_rx) = futures::channel::mpsc::channel::<Outgoing>(1000);
+        let (control_tx, control_rx) = futures::channel::mpsc::unbounded();
+        let (outbox, expired) = Outbox::load(&username);
+        let status = Rc::new(RefCell::new(ConnectionStatus {
+            state: ConnectionState::Connecting,
+            last_error: None,
+        }));
+
+        let supervisor = Supervisor {
+            url: url.to_owned(),
+            username,
+            resume: None,
+            last_seen: None,
+            outbound: in_rx,
+            control: control_rx,
+            outbox,
+            live: VecDeque::new(),
+            backoff: Backoff::default(),
+            event_bus: EventBus::dispatcher(),
+            status: status.clone(),
+        };
+        spawn_local(supervisor.run(expired));
+
+        Self {
+            tx: in_tx,
+            control: control_tx,
+            status,
+        }
+    }
+}
+
+impl Transport for WebsocketService {
+    /// Queues `msg` in the [`Outbox`]; progress is reported as [`OutboxEvent`]s.
+    fn send(&self, msg: &ClientMessage) -> Result<(), Outgoing> {
+        self.tx
+            .clone()
+            .try_send(Outgoing::new(msg))
+            .map_err(|e| e.into_inner())
+    }
+
+    fn close(&self) {
+        let _ = self.control.unbounded_send(Control::Close);
+    }
+
+    /// Skips the rest of the backoff delay if a reconnect is pending.
+    fn reconnect_now(&self) {
+        let _ = self.control.unbounded_send(Control::ReconnectNow);
+    }
+
+    fn status(&self) -> ConnectionStatus {
+        self.status.borrow().clone()
+    }
+}
+
+/// Why a single connection ended.
+enum Closed {
+    /// The socket dropped or never opened; try again.
+    Lost,
+    /// The service was dropped; stop for good.
+    Shutdown,
+    /// The server closed with [`CLOSE_TOKEN_EXPIRED`] or
+    /// [`CLOSE_TOKEN_REJECTED`]; stop for good.
+    Expired,
+}
+
+/// How far a connection is with `register` or `resume`.
+#[derive(Clone, Copy, PartialEq)]
+enum Greeting {
+    Unsent,
+    /// Written; nothing else goes out until the server accepts it.
+    Sent,
+    Accepted,
+}
+
+struct Supervisor {
+    url: String,
+    username: String,
+    /// From the server's last `resumeToken`.
+    resume: Option<String>,
+    /// Id of the newest message received.
+    last_seen: Option<u64>,
+    outbound: Receiver<Outgoing>,
+    /// Requests from the [`WebsocketService`] handle.
+    control: UnboundedReceiver<Control>,
+    /// Frames taken off `outbound` but not yet written to a socket.
+    outbox: Outbox,
+    /// Ephemeral frames waiting for the open socket, sent ahead of `outbox`.
+    live: VecDeque<String>,
+    backoff: Backoff,
+    event_bus: Dispatcher<EventBus>,
+    status: Rc<RefCell<ConnectionStatus>>,
+}
+
+impl Supervisor {
+    async fn run(mut self, expired: Vec<Outgoing>) {
+        for entry in expired {
+            self.report(OutboxEvent::Queued(entry.clone()));
+            self.report(OutboxEvent::Delivery(entry.id, Delivery::Failed));
+        }
+        let restored: Vec<_> = self.outbox.iter().cloned().collect();
+        for entry in restored {
+            self.report(OutboxEvent::Queued(entry));
+        }
+
+        loop {
+            self.publish(ConnectionState::Connecting);
+            let closed = match WebSocket::open(&self.url) {
+                Ok(ws) => self.connection(ws).await,
+                Err(e) => {
+                    self.fail(format!("cannot open {}: {}", self.url, e));
+                    Closed::Lost
+                }
+            };
+            match closed {
+                Closed::Lost => {}
+                Closed::Shutdown => break,
+                Closed::Expired => {
+                    log::info!("session token no longer valid, not reconnecting");
+                    self.publish(ConnectionState::Expired);
+                    return;
+                }
+            }
 
-        let (mut write, mut read) = ws.split();
+            let attempt = self.backoff.attempt + 1;
+            let delay_ms = self.backoff.next_delay();
+            log::info!("WebSocket closed, reconnecting in {}ms", delay_ms);
+            self.publish(ConnectionState::Reconnecting { attempt, delay_ms });
+            if let Closed::Shutdown = self.wait(delay_ms).await {
+                break;
+            }
+        }
+        // Not published: a replacement service may already be reporting.
+        self.status.borrow_mut().state = ConnectionState::Closed;
+        log::debug!("WebSocket service stopped");
+    }
 
-        let (in_tx, mut in_rx) = futures::channel::mpsc::channel::<String>(1000);
-        let mut event_bus = EventBus::dispatcher();
+    fn publish(&mut self, state: ConnectionState) {
+        let status = {
+            let mut current = self.status.borrow_mut();
+            current.state = state;
+            current.clone()
+        };
+        self.event_bus.send(Request::Connection(status));
+    }
 
-        spawn_local(async move {
-            while let Some(s) = in_rx.next().await {
-                log::debug!("got event from channel! {}", s);
-                write.send(Message::Text(s)).await.unwrap();
+    fn report(&mut self, event: OutboxEvent) {
+        self.event_bus.send(Request::Outbox(event));
+    }
+
+    /// Queues `entry` in the outbox, or for an ephemeral frame, sends it only
+    /// if the socket is `open`, without keeping or reporting it.
+    fn enqueue(&mut self, entry: Outgoing, open: bool) {
+        if entry.is_ephemeral() {
+            if open {
+                self.live.push_back(entry.frame);
             }
-        });
-
-        spawn_local(async move {
-            while let Some(msg) = read.next().await {
-                match msg {
-                    Ok(Message::Text(data)) => {
-                        log::debug!("from websocket: {}", data);
-                        event_bus.send(Request::EventBusMsg(data));
+            return;
+        }
+        self.report(OutboxEvent::Queued(entry.clone()));
+        if let Err(entry) = self.outbox.push(entry) {
+            log::warn!("outbox full, dropping frame {}", entry.id);
+            self.report(OutboxEvent::Delivery(entry.id, Delivery::Failed));
+        }
+    }
+
+    /// The first frame on every connection.
+    fn hello(&self) -> String {
+        match &self.resume {
+            Some(token) => ClientMessage::Resume(ResumeData {
+                token: token.clone(),
+                last_seen: self.last_seen,
+            }),
+            None => ClientMessage::Register(self.username.clone()),
+        }
+        .encode()
+    }
+
+    /// Keeps what a reconnect needs from `frame`, then passes it on. The
+    /// server accepting our greeting opens the connection.
+    fn receive(&mut self, frame: String, greeting: &mut Greeting) {
+        log::debug!("from websocket: {}", frame);
+        match ServerMessage::decode(&frame) {
+            Ok(ServerMessage::RegisterAck(_)) if *greeting == Greeting::Sent => {
+                *greeting = Greeting::Accepted;
+                self.backoff.reset();
+                self.publish(ConnectionState::Open);
+            }
+            Ok(ServerMessage::ResumeToken(token)) => self.resume = Some(token),
+            Ok(ServerMessage::Message(data) | ServerMessage::Direct(data))
+                if data.id > self.last_seen =>
+            {
+                self.last_seen = data.id;
+            }
+            _ => {}
+        }
+        self.event_bus.send(Request::EventBusMsg(frame));
+    }
+
+    fn fail(&mut self, error: String) {
+        log::error!("{}", error);
+        self.status.borrow_mut().last_error = Some(error);
+    }
+
+    /// Pumps frames in both directions until the socket goes away.
+    async fn connection(&mut self, ws: WebSocket) -> Closed {
+        let (mut write, read) = ws.split();
+        let mut read = read.fuse();
+        let mut greeting = Greeting::Unsent;
+        self.live.clear();
+
+        loop {
+            let open = greeting == Greeting::Accepted;
+            let live = open && !self.live.is_empty();
+            let next = match greeting {
+                _ if live => self.live.front().cloned(),
+                Greeting::Unsent => Some(self.hello()),
+                // A rejection keeps the outbox for a later connection.
+                Greeting::Sent => None,
+                Greeting::Accepted => self.outbox.front().map(|e| e.frame.clone()),
+            };
+            // A send stays pending until the socket has opened; it is rebuilt
+            // from the outbox each round so nothing is lost if it is dropped.
+            let send = match next {
+                Some(frame) => write.send(Message::Text(frame)).fuse(),
+                None => Fuse::terminated(),
+            };
+            pin_mut!(send);
+
+            select! {
+                frame = read.next() => match frame {
+                    Some(Ok(Message::Text(data))) => self.receive(data, &mut greeting),
+                    Some(Ok(Message::Bytes(b))) => {
+                        if let Ok(val) = std::str::from_utf8(&b) {
+                            self.receive(val.into(), &mut greeting);
+                        }
+                    }
+                    Some(Err(WebSocketError::ConnectionClose(e)))
+                        if e.code == CLOSE_TOKEN_EXPIRED || e.code == CLOSE_TOKEN_REJECTED =>
+                    {
+                        self.fail(format!("session token refused: {} (code {})", e.reason, e.code));
+                        return Closed::Expired;
+                    }
+                    Some(Err(WebSocketError::ConnectionClose(e))) if !e.was_clean => {
+                        self.fail(format!("connection closed (code {})", e.code));
+                    }
+                    Some(Err(WebSocketError::ConnectionClose(e))) => {
+                        log::info!("ws closed: code {} {}", e.code, e.reason);
                     }
-                    Ok(Message::Bytes(b)) => {
-                        let decoded = std::str::from_utf8(&b);
-                        if let Ok(val) = decoded {
-                            log::debug!("from websocket: {}", val);
-                            event_bus.send(Request::EventBusMsg(val.into()));
+                    Some(Err(e)) => self.fail(e.to_string()),
+                    None => return Closed::Lost,
+                },
+                entry = self.outbound.next() => match entry {
+                    Some(entry) => self.enqueue(entry, open),
+                    None => return Closed::Shutdown,
+                },
+                control = self.control.next() => match control {
+                    // Already connected, nothing to skip.
+                    Some(Control::ReconnectNow) => {}
+                    Some(Control::Close) | None => return Closed::Shutdown,
+                },
+                sent = send => match sent {
+                    Ok(()) if live => {
+                        self.live.pop_front();
+                    }
+                    Ok(()) if open => {
+                        if let Some(entry) = self.outbox.pop_front() {
+                            self.report(OutboxEvent::Delivery(entry.id, Delivery::Written));
                         }
                     }
+                    Ok(()) => greeting = Greeting::Sent,
                     Err(e) => {
-                        log::error!("ws: {:?}", e)
+                        self.fail(e.to_string());
+                        return Closed::Lost;
                     }
-                }
+                },
             }
-            log::debug!("WebSocket Closed");
-        });
+        }
+    }
 
-        Self { tx: in_tx }
+    /// Sleeps for `delay_ms` while still accepting outbound frames. Returns
+    /// early when asked to reconnect now or to close.
+    async fn wait(&mut self, delay_ms: u32) -> Closed {
+        let timer = TimeoutFuture::new(delay_ms).fuse();
+        pin_mut!(timer);
+        while !timer.is_terminated() {
+            select! {
+                _ = timer => {}
+                control = self.control.next() => match control {
+                    Some(Control::ReconnectNow) => break,
+                    Some(Control::Close) | None => return Closed::Shutdown,
+                },
+                entry = self.outbound.next() => match entry {
+                    Some(entry) => self.enqueue(entry, false),
+                    None => return Closed::Shutdown,
+                },
+            }
+        }
+        Closed::Lost
     }
-}
\ No newline at end of file
+}
+
+/// Exponential backoff with "equal jitter": half of each delay is fixed, the
+/// other half random, so clients dropped together do not reconnect together.
+#[derive(Default)]
+struct Backoff {
+    attempt: u32,
+}
+
+impl Backoff {
+    fn next_delay(&mut self) -> u32 {
+        let ceiling = BACKOFF_INITIAL_MS
+            .saturating_mul(1 << self.attempt.min(16))
+            .min(BACKOFF_MAX_MS);
+        self.attempt += 1;
+        let half = ceiling / 2;
+        half + (js_sys::Math::random() * half as f64) as u32
+    }
+
+    fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
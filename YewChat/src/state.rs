@@ -0,0 +1,817 @@
+//! Chat behaviour as a plain reducer, free of DOM and socket access so it can
+//! be tested with `cargo test` on the host.
+//!
+//! [`Chat`](crate::components::chat::Chat) feeds every inbound event and user
+//! intent into [`ChatState::reduce`], renders the resulting state and carries
+//! out the returned [`Command`]s.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use yewchat_protocol::{
+    link_mentions, mentions, validate_room, ClientMessage, DirectData, EditData, MessageData,
+    PostData, ReactionData, ReadData, ServerMessage, TypingData, LOBBY,
+};
+
+use crate::services::outbox::{Delivery, OutboxEvent, Outgoing};
+use crate::services::websocket::{ConnectionState, ConnectionStatus};
+
+/// While we keep typing, `typing` is sent again at most this often.
+pub const TYPING_THROTTLE_MS: f64 = 3_000.0;
+/// We count as stopped after this long without a keystroke.
+pub const TYPING_IDLE_MS: f64 = 5_000.0;
+/// Someone else counts as stopped when not heard from for this long, in case
+/// they vanished before saying so.
+pub const TYPING_TIMEOUT_MS: f64 = 8_000.0;
+/// Nicknames offered at most when autocompleting a mention.
+pub const MAX_SUGGESTIONS: usize = 5;
+/// Nicknames named at most on a reaction chip; the rest are counted.
+pub const MAX_REACTORS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserProfile {
+    pub name: String,
+    pub avatar: String,
+}
+
+impl UserProfile {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            avatar: avatar_url(name),
+        }
+    }
+}
+
+pub fn avatar_url(name: &str) -> String {
+    format!(
+        "https://avatars.dicebear.com/api/adventurer-neutral/{}.svg",
+        name
+    )
+}
+
+/// Where a line is said.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conversation {
+    Room(String),
+    /// In private with the given nickname.
+    Direct(String),
+}
+
+/// A line we sent that has not come back from the server yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalMessage {
+    pub id: u64,
+    pub to: Conversation,
+    /// The thread it replies in.
+    pub parent_id: Option<u64>,
+    pub text: String,
+    pub queued_at: f64,
+    pub delivery: Delivery,
+}
+
+impl LocalMessage {
+    fn new(entry: Outgoing, delivery: Delivery) -> Option<Self> {
+        let (to, parent_id, text) = match (entry.post(), entry.direct()) {
+            (Some(post), _) => (Conversation::Room(post.room), post.parent_id, post.message),
+            (None, Some(direct)) => (
+                Conversation::Direct(direct.to),
+                direct.parent_id,
+                direct.message,
+            ),
+            (None, None) => return None,
+        };
+        Some(Self {
+            id: entry.id,
+            to,
+            parent_id,
+            text,
+            queued_at: entry.queued_at,
+            delivery,
+        })
+    }
+}
+
+/// Where we are typing, as last told to the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Composing {
+    pub to: Conversation,
+    /// When `typing` was last sent.
+    pub sent: f64,
+    /// When the input last changed.
+    pub input: f64,
+}
+
+/// Everything that can happen to a chat.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// A raw frame arrived from the server.
+    Frame(String),
+    /// The connection changed state.
+    Connection(ConnectionStatus),
+    /// A queued frame moved through the outbox.
+    Outbox(OutboxEvent),
+    /// The user changed an input box to the given text, at the given time
+    /// in milliseconds.
+    Input(String, f64),
+    /// The user submitted the input box.
+    Submit(String),
+    /// The user submitted the input box of the open thread.
+    Reply(String),
+    /// The user asked to resend a failed line.
+    Retry(u64),
+    /// The user opened a room, joining it if we are not in it yet. Names
+    /// the server would refuse are ignored.
+    View(String),
+    /// The user left a room.
+    Leave(String),
+    /// The user opened the direct conversation with a nickname.
+    Direct(String),
+    /// The user changed the text of one of their messages, by id.
+    Edit(u64, String),
+    /// The user deleted one of their messages, by id.
+    Delete(u64),
+    /// The user picked an emoji under a message, by id: adds our reaction,
+    /// or takes it back if we already reacted with it.
+    React(u64, String),
+    /// The user opened the thread under a message, by id.
+    OpenThread(u64),
+    CloseThread,
+    /// The user switched between all messages and only those mentioning us.
+    ToggleMentions,
+    /// Ask the server for the room list and the members of our rooms, as
+    /// after (re)connecting.
+    Sync,
+    /// Time passed; the current time in milliseconds.
+    Tick(f64),
+    /// The conversation on screen was shown in a focused window.
+    Seen,
+}
+
+/// Side effects the reducer asks its host to perform.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Send(ClientMessage),
+}
+
+/// A room we are in, or a direct conversation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Room {
+    /// For the lobby, everyone online. Empty for direct conversations.
+    pub members: Vec<UserProfile>,
+    pub messages: Vec<MessageData>,
+    /// Lines from others since we last looked at the room.
+    pub unread: usize,
+    /// Who is typing here, and when we last heard so.
+    pub typing: BTreeMap<String, f64>,
+    /// Id of the newest message each user has read here, ourselves included.
+    pub reads: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatState {
+    pub username: String,
+    pub users: Vec<UserProfile>,
+    /// The room on screen, or last on screen; always one of `rooms`.
+    pub room: String,
+    /// The rooms we are in, the lobby included.
+    pub rooms: BTreeMap<String, Room>,
+    /// The nickname whose direct conversation is on screen instead of
+    /// `room`; always one of `directs`.
+    pub direct: Option<String>,
+    /// Direct conversations by the other nickname.
+    pub directs: BTreeMap<String, Room>,
+    /// Id of the message whose thread is open beside the conversation.
+    pub thread: Option<u64>,
+    /// Whether only messages mentioning us are shown.
+    pub mentions_only: bool,
+    /// Set while we are typing.
+    pub composing: Option<Composing>,
+    /// The time in milliseconds as of the last [`Action::Tick`] or
+    /// [`Action::Input`].
+    pub now: f64,
+    /// Every room on the server, as last listed.
+    pub directory: Vec<String>,
+    /// Our own lines, shown after `messages` until the server echoes them.
+    pub outbox: Vec<LocalMessage>,
+    /// Frames dropped because they could not be decoded.
+    pub decode_errors: usize,
+    pub status: ConnectionStatus,
+    /// Why the server last refused our nickname, typically when a reconnect
+    /// finds it taken. Cleared by the next acceptance.
+    pub rejected: Option<String>,
+}
+
+impl ChatState {
+    pub fn new(username: String, status: ConnectionStatus) -> Self {
+        Self {
+            username,
+            users: vec![],
+            room: LOBBY.into(),
+            rooms: BTreeMap::from([(LOBBY.to_owned(), Room::default())]),
+            direct: None,
+            directs: BTreeMap::new(),
+            thread: None,
+            mentions_only: false,
+            composing: None,
+            now: 0.0,
+            directory: vec![LOBBY.into()],
+            outbox: vec![],
+            decode_errors: 0,
+            status,
+            rejected: None,
+        }
+    }
+
+    /// Applies `action` and returns what should be sent as a result.
+    pub fn reduce(&mut self, action: Action) -> Vec<Command> {
+        let before = self.conversation();
+        let mut commands = self.apply(action);
+        // Going elsewhere ends our typing where we were.
+        if self.conversation() != before {
+            commands.splice(0..0, self.stop_typing());
+        }
+        commands
+    }
+
+    fn apply(&mut self, action: Action) -> Vec<Command> {
+        match action {
+            Action::Frame(s) => match ServerMessage::decode(&s) {
+                Ok(msg) => return self.receive(msg),
+                Err(e) => {
+                    self.decode_errors += 1;
+                    log::warn!(
+                        "dropping frame ({} so far): {}: {}",
+                        self.decode_errors,
+                        e,
+                        s
+                    );
+                }
+            },
+            Action::Connection(status) => {
+                // Anything we said about typing was lost with the socket, and
+                // so may be our last read markers; they are sent again once
+                // the conversation is seen on the next connection.
+                if status.state != ConnectionState::Open {
+                    self.composing = None;
+                    for room in self.rooms.values_mut().chain(self.directs.values_mut()) {
+                        room.reads.remove(&self.username);
+                    }
+                }
+                self.status = status;
+            }
+            Action::Outbox(OutboxEvent::Queued(entry)) => {
+                if !self.outbox.iter().any(|m| m.id == entry.id) {
+                    self.outbox
+                        .extend(LocalMessage::new(entry, Delivery::Pending));
+                }
+            }
+            Action::Outbox(OutboxEvent::Delivery(id, delivery)) => {
+                if let Some(local) = self.outbox.iter_mut().find(|m| m.id == id) {
+                    local.delivery = delivery;
+                }
+            }
+            Action::Input(text, now) => {
+                self.now = now;
+                return self.start_typing(&text);
+            }
+            Action::Submit(text) => {
+                if !text.trim().is_empty() {
+                    let line = say(self.conversation(), None, self.link(&text));
+                    return std::iter::once(line).chain(self.stop_typing()).collect();
+                }
+            }
+            Action::Reply(text) => {
+                if self.thread.is_some() && !text.trim().is_empty() {
+                    let line = say(self.conversation(), self.thread, self.link(&text));
+                    return std::iter::once(line).chain(self.stop_typing()).collect();
+                }
+            }
+            Action::Retry(id) => {
+                if let Some(i) = self
+                    .outbox
+                    .iter()
+                    .position(|m| m.id == id && m.delivery == Delivery::Failed)
+                {
+                    let local = self.outbox.remove(i);
+                    return vec![say(local.to, local.parent_id, local.text)];
+                }
+            }
+            Action::View(room) => {
+                if validate_room(&room).is_err() {
+                    return vec![];
+                }
+                self.direct = None;
+                self.thread = None;
+                self.room = room.clone();
+                match self.rooms.get_mut(&room) {
+                    Some(seen) => seen.unread = 0,
+                    None => {
+                        self.rooms.insert(room.clone(), Room::default());
+                        return vec![Command::Send(ClientMessage::Join(room))];
+                    }
+                }
+            }
+            Action::Leave(room) => {
+                if room != LOBBY && self.rooms.remove(&room).is_some() {
+                    if self.room == room {
+                        self.room = LOBBY.into();
+                    }
+                    let gone = Conversation::Room(room.clone());
+                    self.outbox.retain(|m| m.to != gone);
+                    return vec![Command::Send(ClientMessage::Leave(room))];
+                }
+            }
+            Action::Direct(nick) => {
+                if nick != self.username {
+                    self.directs.entry(nick.clone()).or_default().unread = 0;
+                    self.direct = Some(nick);
+                    self.thread = None;
+                }
+            }
+            Action::Edit(id, text) => {
+                let text = self.link(&text);
+                let changed = self
+                    .own(id)
+                    .is_some_and(|m| m.message != text && !text.trim().is_empty());
+                if changed {
+                    return vec![Command::Send(ClientMessage::Edit(EditData {
+                        id,
+                        message: text,
+                    }))];
+                }
+            }
+            Action::Delete(id) => {
+                if self.own(id).is_some() {
+                    return vec![Command::Send(ClientMessage::Delete(id))];
+                }
+            }
+            Action::React(id, emoji) => {
+                let on_screen = self
+                    .current()
+                    .messages
+                    .iter()
+                    .find(|m| m.id == Some(id) && !m.deleted);
+                if let Some(m) = on_screen {
+                    let reacted = m.reacted(&emoji, &self.username);
+                    let reaction = ReactionData {
+                        id,
+                        emoji,
+                        from: None,
+                    };
+                    return vec![Command::Send(if reacted {
+                        ClientMessage::Unreact(reaction)
+                    } else {
+                        ClientMessage::React(reaction)
+                    })];
+                }
+            }
+            Action::OpenThread(id) => self.thread = Some(id),
+            Action::CloseThread => self.thread = None,
+            Action::ToggleMentions => self.mentions_only = !self.mentions_only,
+            Action::Sync => return self.sync(),
+            Action::Tick(now) => {
+                self.now = now;
+                for room in self.rooms.values_mut().chain(self.directs.values_mut()) {
+                    room.typing
+                        .retain(|_, heard| now - *heard < TYPING_TIMEOUT_MS);
+                }
+                let idle = self
+                    .composing
+                    .as_ref()
+                    .is_some_and(|c| now - c.input >= TYPING_IDLE_MS);
+                if idle {
+                    return self.stop_typing().into_iter().collect();
+                }
+            }
+            Action::Seen => {
+                if self.status.state != ConnectionState::Open {
+                    return vec![];
+                }
+                let Some(id) = self.messages().iter().filter_map(|m| m.id).max() else {
+                    return vec![];
+                };
+                let username = self.username.clone();
+                let read = self.current_mut().reads.entry(username).or_default();
+                if *read < id {
+                    *read = id;
+                    return vec![Command::Send(ClientMessage::Read(ReadData {
+                        id,
+                        from: None,
+                    }))];
+                }
+            }
+        }
+        vec![]
+    }
+
+    /// Tells the others we are typing in the conversation on screen, unless
+    /// we did so recently, or that we stopped if `text` is blank.
+    fn start_typing(&mut self, text: &str) -> Vec<Command> {
+        if text.trim().is_empty() {
+            return self.stop_typing().into_iter().collect();
+        }
+        if self.status.state != ConnectionState::Open {
+            return vec![];
+        }
+        let to = self.conversation();
+        let now = self.now;
+        match &mut self.composing {
+            Some(c) if c.to == to && now - c.sent < TYPING_THROTTLE_MS => {
+                c.input = now;
+                vec![]
+            }
+            _ => {
+                let frame = typing_frame(&to, true);
+                self.composing = Some(Composing {
+                    to,
+                    sent: now,
+                    input: now,
+                });
+                vec![frame]
+            }
+        }
+    }
+
+    /// Tells the others we stopped typing, if we said we were.
+    fn stop_typing(&mut self) -> Option<Command> {
+        let composing = self.composing.take()?;
+        Some(typing_frame(&composing.to, false))
+    }
+
+    fn sync(&self) -> Vec<Command> {
+        std::iter::once(ClientMessage::ListRooms)
+            .chain(self.rooms.keys().cloned().map(ClientMessage::Join))
+            .map(Command::Send)
+            .collect()
+    }
+
+    fn receive(&mut self, msg: ServerMessage) -> Vec<Command> {
+        match msg {
+            ServerMessage::Users(names) => {
+                self.users = profiles(&names);
+                self.rooms.entry(LOBBY.into()).or_default().members = profiles(&names);
+            }
+            ServerMessage::Rooms(rooms) => self.directory = rooms,
+            // The server only tells members about a room, so a room we do not
+            // know yet is one we were put back in after a reload.
+            ServerMessage::Members(members) => {
+                self.rooms.entry(members.room).or_default().members = profiles(&members.users);
+            }
+            ServerMessage::Message(message_data) => {
+                self.append(Conversation::Room(message_data.room.clone()), message_data);
+            }
+            ServerMessage::Direct(message_data) => {
+                let Some(to) = message_data.to.clone() else {
+                    log::warn!("direct message without a recipient: {:?}", message_data);
+                    return vec![];
+                };
+                let peer = if message_data.from == self.username {
+                    to
+                } else {
+                    message_data.from.clone()
+                };
+                self.append(Conversation::Direct(peer), message_data);
+            }
+            ServerMessage::DirectRejected(rejected) => {
+                log::info!("direct message refused: {}", rejected.reason);
+                let direct = rejected.direct;
+                let to = Conversation::Direct(direct.to);
+                if let Some(i) = unechoed(&self.outbox, &to, direct.parent_id, &direct.message) {
+                    self.outbox[i].delivery = Delivery::Failed;
+                }
+            }
+            ServerMessage::Edit(message_data) => {
+                let id = message_data.id;
+                if let Some(m) = id.and_then(|id| self.message_mut(id)) {
+                    *m = message_data;
+                }
+            }
+            ServerMessage::Delete(id) => {
+                if let Some(m) = self.message_mut(id) {
+                    m.delete();
+                }
+            }
+            ServerMessage::Typing(typing) => {
+                let Some(from) = typing.from else {
+                    return vec![];
+                };
+                let room = match typing.to {
+                    Some(_) => self.directs.get_mut(&from),
+                    None => self.rooms.get_mut(&typing.room),
+                };
+                let Some(room) = room else {
+                    return vec![];
+                };
+                if typing.typing {
+                    room.typing.insert(from, self.now);
+                } else {
+                    room.typing.remove(&from);
+                }
+            }
+            ServerMessage::Read(read) => {
+                let Some(from) = read.from else {
+                    return vec![];
+                };
+                let room = self
+                    .rooms
+                    .values_mut()
+                    .chain(self.directs.values_mut())
+                    .find(|room| room.messages.iter().any(|m| m.id == Some(read.id)));
+                if let Some(room) = room {
+                    let marker = room.reads.entry(from).or_default();
+                    *marker = read.id.max(*marker);
+                }
+            }
+            ServerMessage::React(reaction) => self.react(reaction, true),
+            ServerMessage::Unreact(reaction) => self.react(reaction, false),
+            ServerMessage::RegisterAck(_) => {
+                self.rejected = None;
+                // After a reconnect the server may have forgotten our rooms.
+                return self.sync();
+            }
+            ServerMessage::RegisterRejected(reason) => self.rejected = Some(reason),
+            // Kept by the connection, not the chat.
+            ServerMessage::ResumeToken(_) => {}
+        }
+        vec![]
+    }
+
+    /// Adds a line to the conversation it belongs to, taking it out of the
+    /// outbox if it is the echo of one of ours.
+    fn append(&mut self, to: Conversation, message_data: MessageData) {
+        let on_screen = to == self.conversation();
+        let room = match &to {
+            Conversation::Room(room) => self.rooms.entry(room.clone()).or_default(),
+            Conversation::Direct(nick) => self.directs.entry(nick.clone()).or_default(),
+        };
+        // A resume may replay a line we already have.
+        let seen =
+            message_data.id.is_some() && room.messages.iter().any(|m| m.id == message_data.id);
+        if seen {
+            return;
+        }
+        if message_data.from == self.username {
+            let echoed = unechoed(
+                &self.outbox,
+                &to,
+                message_data.parent_id,
+                &message_data.message,
+            );
+            if let Some(i) = echoed {
+                self.outbox.remove(i);
+            }
+        } else if !on_screen {
+            room.unread += 1;
+        }
+        // Whoever sent it is done typing it.
+        room.typing.remove(&message_data.from);
+        room.messages.push(message_data);
+    }
+
+    /// Counts a reaction the server passed on, or takes it back.
+    fn react(&mut self, reaction: ReactionData, add: bool) {
+        let (Some(from), Some(m)) = (reaction.from, self.message_mut(reaction.id)) else {
+            return;
+        };
+        if add {
+            m.react(&reaction.emoji, &from);
+        } else {
+            m.unreact(&reaction.emoji, &from);
+        }
+    }
+
+    /// `text` as typed, with `@nick` linked for everyone online.
+    fn link(&self, text: &str) -> String {
+        link_mentions(text, |nick| self.users.iter().any(|u| u.name == nick))
+    }
+
+    /// Message `id` wherever it was said. Ids are unique across the server.
+    fn message_mut(&mut self, id: u64) -> Option<&mut MessageData> {
+        self.rooms
+            .values_mut()
+            .chain(self.directs.values_mut())
+            .flat_map(|room| room.messages.iter_mut())
+            .find(|m| m.id == Some(id))
+    }
+
+    /// Message `id` on screen, if we wrote it and it still stands.
+    pub fn own(&self, id: u64) -> Option<&MessageData> {
+        self.current()
+            .messages
+            .iter()
+            .find(|m| m.id == Some(id) && m.from == self.username && !m.deleted)
+    }
+
+    /// The conversation on screen.
+    pub fn conversation(&self) -> Conversation {
+        match &self.direct {
+            Some(nick) => Conversation::Direct(nick.clone()),
+            None => Conversation::Room(self.room.clone()),
+        }
+    }
+
+    /// The room or direct conversation on screen.
+    pub fn current(&self) -> &Room {
+        match &self.direct {
+            Some(nick) => &self.directs[nick],
+            None => &self.rooms[&self.room],
+        }
+    }
+
+    fn current_mut(&mut self) -> &mut Room {
+        match &self.direct {
+            Some(nick) => self
+                .directs
+                .get_mut(nick)
+                .expect("open direct conversation"),
+            None => self.rooms.get_mut(&self.room).expect("open room"),
+        }
+    }
+
+    /// Who else has read up to each message on screen: a user shows up under
+    /// the newest line they have read, by its id.
+    pub fn read_markers(&self) -> BTreeMap<u64, Vec<&str>> {
+        let ids: Vec<u64> = self.messages().iter().filter_map(|m| m.id).collect();
+        let mut markers: BTreeMap<u64, Vec<&str>> = BTreeMap::new();
+        for (nick, &read) in &self.current().reads {
+            if *nick == self.username {
+                continue;
+            }
+            if let Some(&id) = ids.iter().rev().find(|&&id| id <= read) {
+                markers.entry(id).or_default().push(nick);
+            }
+        }
+        markers
+    }
+
+    /// Messages in the conversation on screen, without the replies in
+    /// threads under them. Replies to messages we never got stand alone.
+    pub fn messages(&self) -> Vec<&MessageData> {
+        let messages = &self.current().messages;
+        let ids: BTreeSet<u64> = messages.iter().filter_map(|m| m.id).collect();
+        messages
+            .iter()
+            .filter(|m| m.parent_id.is_none_or(|parent| !ids.contains(&parent)))
+            .collect()
+    }
+
+    /// Who else is typing in the conversation on screen, by name.
+    pub fn typing(&self) -> Vec<&str> {
+        self.current().typing.keys().map(String::as_str).collect()
+    }
+
+    /// Whether `m` mentions us.
+    pub fn mentions_me(&self, m: &MessageData) -> bool {
+        mentions(&m.message).contains(&self.username.as_str())
+    }
+
+    /// What the timeline shows: [`messages`](Self::messages), or with
+    /// `mentions_only` every message mentioning us, replies included.
+    pub fn timeline(&self) -> Vec<&MessageData> {
+        if !self.mentions_only {
+            return self.messages();
+        }
+        self.current()
+            .messages
+            .iter()
+            .filter(|m| self.mentions_me(m))
+            .collect()
+    }
+
+    /// Those online, other than us, whose nickname starts with the `@word`
+    /// being typed at the end of `text`, for autocomplete.
+    pub fn suggestions(&self, text: &str) -> Vec<&str> {
+        let Some(prefix) = mention_prefix(text) else {
+            return vec![];
+        };
+        let prefix = prefix.to_lowercase();
+        self.users
+            .iter()
+            .map(|u| u.name.as_str())
+            .filter(|name| *name != self.username && name.to_lowercase().starts_with(&prefix))
+            .take(MAX_SUGGESTIONS)
+            .collect()
+    }
+
+    /// How many replies there are in the thread under message `id`.
+    pub fn replies(&self, id: u64) -> usize {
+        self.current()
+            .messages
+            .iter()
+            .filter(|m| m.parent_id == Some(id))
+            .count()
+    }
+
+    /// The open thread: its first message and the replies under it, if it is
+    /// in the conversation on screen.
+    pub fn open_thread(&self) -> Option<(&MessageData, Vec<&MessageData>)> {
+        let id = self.thread?;
+        let messages = &self.current().messages;
+        let parent = messages.iter().find(|m| m.id == Some(id))?;
+        let replies = messages
+            .iter()
+            .filter(|m| m.parent_id == Some(id))
+            .collect();
+        Some((parent, replies))
+    }
+
+    /// Every room to list: the lobby, then the others on the server or that
+    /// we are in, by name.
+    pub fn room_list(&self) -> Vec<String> {
+        let mut rooms: Vec<String> = self
+            .directory
+            .iter()
+            .chain(self.rooms.keys())
+            .filter(|r| *r != LOBBY)
+            .cloned()
+            .collect();
+        rooms.sort();
+        rooms.dedup();
+        rooms.insert(0, LOBBY.into());
+        rooms
+    }
+
+    /// Avatar for `name`, even if they are no longer online.
+    pub fn avatar(&self, name: &str) -> String {
+        self.users
+            .iter()
+            .find(|u| u.name == name)
+            .map(|u| u.avatar.clone())
+            .unwrap_or_else(|| avatar_url(name))
+    }
+}
+
+fn profiles(names: &[String]) -> Vec<UserProfile> {
+    names.iter().map(|u| UserProfile::new(u)).collect()
+}
+
+fn say(to: Conversation, parent_id: Option<u64>, message: String) -> Command {
+    Command::Send(match to {
+        Conversation::Room(room) => ClientMessage::Post(PostData {
+            room,
+            message,
+            parent_id,
+        }),
+        Conversation::Direct(to) => ClientMessage::Direct(DirectData {
+            to,
+            message,
+            parent_id,
+        }),
+    })
+}
+
+/// Where in `outbox` the oldest line of ours in `to` with this text is that
+/// the server has not answered yet.
+fn unechoed(
+    outbox: &[LocalMessage],
+    to: &Conversation,
+    parent_id: Option<u64>,
+    text: &str,
+) -> Option<usize> {
+    outbox.iter().position(|m| {
+        m.delivery != Delivery::Failed && m.to == *to && m.parent_id == parent_id && m.text == text
+    })
+}
+
+fn typing_frame(to: &Conversation, typing: bool) -> Command {
+    let (room, to) = match to {
+        Conversation::Room(room) => (room.clone(), None),
+        Conversation::Direct(nick) => (LOBBY.to_owned(), Some(nick.clone())),
+    };
+    Command::Send(ClientMessage::Typing(TypingData {
+        room,
+        to,
+        from: None,
+        typing,
+    }))
+}
+
+/// Who picked a reaction, as written on its chip: us as "Anda", and anyone
+/// past the first [`MAX_REACTORS`] only counted.
+pub fn reactors(nicks: &[String], username: &str) -> String {
+    let named: Vec<&str> = nicks
+        .iter()
+        .take(MAX_REACTORS)
+        .map(|nick| if nick == username { "Anda" } else { nick })
+        .collect();
+    match nicks.len().saturating_sub(MAX_REACTORS) {
+        0 => named.join(", "),
+        more => format!("{} +{}", named.join(", "), more),
+    }
+}
+
+/// The partial nickname after an `@` that starts the last word of `text`.
+fn mention_prefix(text: &str) -> Option<&str> {
+    let word = text.rsplit(char::is_whitespace).next()?;
+    word.strip_prefix('@')
+}
+
+/// `text` with the `@word` being typed at its end completed to `@nick`,
+/// ready for the next word.
+pub fn complete_mention(text: &str, nick: &str) -> String {
+    let Some(prefix) = mention_prefix(text) else {
+        return text.to_owned();
+    };
+    let start = text.len() - prefix.len() - 1;
+    format!("{}@{} ", &text[..start], nick)
+}
@@ -0,0 +1,165 @@
+//! App-wide state that any component can read and subscribe to.
+//!
+//! `Main` owns the [`Store`] and hands it out through a `ContextProvider`.
+//! Function components use [`use_store`]; struct components call
+//! [`Store::subscribe`] from `create` and keep the [`Subscription`].
+
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+
+use yew::prelude::*;
+use yew_agent::use_bridge;
+use yewchat_protocol::AuthToken;
+
+use crate::services::event_bus::{Event, EventBus};
+use crate::services::websocket::ConnectionStatus;
+
+/// Preferences picked on the login screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// The normalized endpoint to connect to.
+    pub server: String,
+    /// Whether to remember the session for the next visit.
+    pub remember: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppState {
+    /// The nickname the server accepted; `None` until then.
+    pub user: Option<String>,
+    /// The token `user` connected with.
+    pub token: Option<AuthToken>,
+    pub settings: Settings,
+    pub connection: ConnectionStatus,
+}
+
+impl AppState {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            user: None,
+            token: None,
+            settings,
+            connection: ConnectionStatus::default(),
+        }
+    }
+}
+
+/// Shared handle to the [`AppState`]. Clones share the same state.
+#[derive(Clone)]
+pub struct Store {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    state: RefCell<AppState>,
+    subscribers: RefCell<Vec<(usize, Callback<AppState>)>>,
+    next_id: Cell<usize>,
+}
+
+impl PartialEq for Store {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Store {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            inner: Rc::new(Inner {
+                state: RefCell::new(state),
+                subscribers: RefCell::new(vec![]),
+                next_id: Cell::new(0),
+            }),
+        }
+    }
+
+    /// A snapshot of the current state.
+    pub fn get(&self) -> AppState {
+        self.inner.state.borrow().clone()
+    }
+
+    /// Changes the state through `f`, then notifies every subscriber if
+    /// anything actually changed.
+    pub fn update(&self, f: impl FnOnce(&mut AppState)) {
+        let state = {
+            let mut state = self.inner.state.borrow_mut();
+            let before = state.clone();
+            f(&mut state);
+            if *state == before {
+                return;
+            }
+            state.clone()
+        };
+        // Collected first so subscribers may update or subscribe in turn.
+        let subscribers: Vec<_> = self
+            .inner
+            .subscribers
+            .borrow()
+            .iter()
+            .map(|(_, cb)| cb.clone())
+            .collect();
+        for cb in subscribers {
+            cb.emit(state.clone());
+        }
+    }
+
+    /// Calls `callback` with the new state after every change, until the
+    /// returned [`Subscription`] is dropped.
+    pub fn subscribe(&self, callback: Callback<AppState>) -> Subscription {
+        let id = self.inner.next_id.get();
+        self.inner.next_id.set(id + 1);
+        self.inner.subscribers.borrow_mut().push((id, callback));
+        Subscription {
+            store: Rc::downgrade(&self.inner),
+            id,
+        }
+    }
+}
+
+/// Keeps a [`Store::subscribe`] callback registered while alive.
+pub struct Subscription {
+    store: Weak<Inner>,
+    id: usize,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(inner) = self.store.upgrade() {
+            inner
+                .subscribers
+                .borrow_mut()
+                .retain(|(id, _)| *id != self.id);
+        }
+    }
+}
+
+/// The store from context and its current state; the calling component
+/// re-renders whenever the state changes.
+pub fn use_store() -> (Store, AppState) {
+    let store = use_context::<Store>().expect("No context found.");
+    let state = use_state_eq(|| store.get());
+    {
+        let state = state.clone();
+        use_effect_with_deps(
+            move |store| {
+                // Catch anything that changed between render and subscribing.
+                state.set(store.get());
+                let subscription = store.subscribe(Callback::from(move |s| state.set(s)));
+                move || drop(subscription)
+            },
+            store.clone(),
+        );
+    }
+    (store, (*state).clone())
+}
+
+/// Mirrors connection changes from the [`EventBus`] into `store` for as long
+/// as the calling component is mounted.
+pub fn use_connection_feed(store: &Store) {
+    let store = store.clone();
+    use_bridge::<EventBus, _>(move |event| {
+        if let Event::Connection(status) = event {
+            store.update(|state| state.connection = status);
+        }
+    });
+}
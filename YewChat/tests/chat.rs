@@ -0,0 +1,190 @@
+//! Drives `Chat` against a `LoopbackTransport` in a headless browser.
+//! Run with `wasm-pack test --headless --firefox` (or `--chrome`).
+#![cfg(target_arch = "wasm32")]
+// Same `html!` expansion lints the library allows.
+#![allow(clippy::let_unit_value, clippy::unnecessary_operation)]
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use gloo_timers::future::TimeoutFuture;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_test::*;
+use web_sys::{Element, HtmlElement, HtmlInputElement};
+use yew::prelude::*;
+use yew_router::prelude::*;
+use yewchat::components::chat::Chat;
+use yewchat::services::connection::Connection;
+use yewchat::services::loopback::LoopbackTransport;
+use yewchat::services::transport::Transport;
+use yewchat::store::{use_connection_feed, AppState, Settings, Store};
+use yewchat_protocol::{ClientMessage, EditData, MessageData, PostData, ServerMessage, LOBBY};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[derive(Properties, PartialEq)]
+struct HarnessProps {
+    store: Store,
+    connection: Connection,
+}
+
+#[function_component(Harness)]
+fn harness(props: &HarnessProps) -> Html {
+    use_connection_feed(&props.store);
+    html! {
+        <ContextProvider<Store> context={props.store.clone()}>
+            <ContextProvider<Connection> context={props.connection.clone()}>
+                <BrowserRouter>
+                    <Chat />
+                </BrowserRouter>
+            </ContextProvider<Connection>>
+        </ContextProvider<Store>>
+    }
+}
+
+/// Mounts `Chat` for `username` and returns its root element and transport.
+fn mount(username: &str) -> (Element, Rc<LoopbackTransport>) {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let root = document.create_element("div").unwrap();
+    document.body().unwrap().append_child(&root).unwrap();
+
+    let transport = Rc::new(LoopbackTransport::new(username));
+    let connection = Connection::default();
+    connection.install(transport.clone());
+    let store = Store::new(AppState {
+        user: Some(username.into()),
+        connection: transport.status(),
+        ..AppState::new(Settings {
+            server: "ws://loopback".into(),
+            remember: false,
+        })
+    });
+
+    yew::start_app_with_props_in_element::<Harness>(
+        root.clone(),
+        HarnessProps { store, connection },
+    );
+    (root, transport)
+}
+
+/// Lets the agent and the scheduler deliver everything pending.
+async fn settle() {
+    TimeoutFuture::new(0).await;
+}
+
+fn message(from: &str, text: &str) -> String {
+    ServerMessage::Message(MessageData {
+        id: None,
+        room: LOBBY.into(),
+        to: None,
+        parent_id: None,
+        from: from.into(),
+        message: text.into(),
+        time: js_sys::Date::now() as u64,
+        edited: None,
+        edits: vec![],
+        deleted: false,
+        reactions: BTreeMap::new(),
+    })
+    .encode()
+}
+
+#[wasm_bindgen_test]
+async fn renders_users_and_messages_from_the_server() {
+    let (root, transport) = mount("alice");
+    settle().await;
+
+    transport.deliver(&ServerMessage::Users(vec!["alice".into(), "bob".into()]).encode());
+    transport.deliver(&message("bob", "halo alice"));
+    settle().await;
+
+    let html = root.inner_html();
+    assert!(html.contains("2 pengguna aktif"), "{}", html);
+    assert!(html.contains("halo alice"), "{}", html);
+    assert!(html.contains("baru saja"), "{}", html);
+}
+
+#[wasm_bindgen_test]
+async fn survives_garbage_frames() {
+    let (root, transport) = mount("alice");
+    settle().await;
+
+    transport.deliver("not json");
+    transport.deliver(r#"{"messageType":"presence","data":"bob"}"#);
+    transport.deliver(&message("bob", "masih hidup"));
+    settle().await;
+
+    let html = root.inner_html();
+    assert!(html.contains("2 pesan tidak terbaca"), "{}", html);
+    assert!(html.contains("masih hidup"), "{}", html);
+}
+
+#[wasm_bindgen_test]
+async fn submitting_sends_a_message_frame() {
+    let (root, transport) = mount("alice");
+    settle().await;
+
+    let input: HtmlInputElement = root
+        .query_selector("input[name=message]")
+        .unwrap()
+        .unwrap()
+        .unchecked_into();
+    input.set_value("apa kabar?");
+    let button: HtmlElement = root
+        .query_selector("button[aria-label=Kirim]")
+        .unwrap()
+        .unwrap()
+        .unchecked_into();
+    button.click();
+    settle().await;
+
+    // A focused window may report the echo read after it.
+    assert!(transport.sent().contains(&ClientMessage::Post(PostData {
+        room: LOBBY.into(),
+        message: "apa kabar?".into(),
+        parent_id: None,
+    })));
+    assert_eq!(input.value(), "");
+    assert!(root.inner_html().contains("apa kabar?"));
+}
+
+/// The first button labelled `text`.
+fn button(root: &Element, text: &str) -> HtmlElement {
+    let buttons = root.query_selector_all("button").unwrap();
+    (0..buttons.length())
+        .filter_map(|i| buttons.item(i))
+        .map(|button| button.unchecked_into::<HtmlElement>())
+        .find(|button| button.text_content().as_deref() == Some(text))
+        .unwrap_or_else(|| panic!("no {:?} button", text))
+}
+
+#[wasm_bindgen_test]
+async fn a_thread_root_is_edited_in_the_timeline_only() {
+    let (root, transport) = mount("alice");
+    settle().await;
+
+    transport
+        .send(&ClientMessage::Post(PostData {
+            room: LOBBY.into(),
+            message: "ada yang pakai yew?".into(),
+            parent_id: None,
+        }))
+        .unwrap();
+    settle().await;
+    button(&root, "↩ Balas di utas").click();
+    settle().await;
+    button(&root, "✏ Ubah").click();
+    settle().await;
+
+    let editors = root.query_selector_all("input[name=edit]").unwrap();
+    assert_eq!(editors.length(), 1, "{}", root.inner_html());
+    let editor: HtmlInputElement = editors.item(0).unwrap().unchecked_into();
+    editor.set_value("ada yang pakai yew 0.19?");
+    button(&root, "Simpan").click();
+    settle().await;
+
+    assert!(transport.sent().contains(&ClientMessage::Edit(EditData {
+        id: 1,
+        message: "ada yang pakai yew 0.19?".into(),
+    })));
+}
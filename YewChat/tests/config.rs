@@ -0,0 +1,26 @@
+use yewchat::services::config::{auth_url, with_token};
+
+#[test]
+fn auth_lives_next_to_the_socket() {
+    assert_eq!(
+        auth_url("ws://127.0.0.1:8080").as_deref(),
+        Some("http://127.0.0.1:8080/auth")
+    );
+    assert_eq!(
+        auth_url("wss://chat.example.com/socket?x=1").as_deref(),
+        Some("https://chat.example.com/auth")
+    );
+    assert_eq!(auth_url("http://chat.example.com"), None);
+}
+
+#[test]
+fn tokens_ride_along_in_the_query() {
+    assert_eq!(
+        with_token("ws://127.0.0.1:8080", "abc.def"),
+        "ws://127.0.0.1:8080?token=abc.def"
+    );
+    assert_eq!(
+        with_token("wss://chat.example.com/?room=1", "abc.def"),
+        "wss://chat.example.com/?room=1&token=abc.def"
+    );
+}
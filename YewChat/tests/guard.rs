@@ -0,0 +1,35 @@
+//! Route recognition needs the page's base URL, so these run in a browser
+//! like `tests/chat.rs`.
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen_test::*;
+use yewchat::components::guard::LoginQuery;
+use yewchat::Route;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn destination(next: Option<&str>) -> Route {
+    LoginQuery {
+        next: next.map(String::from),
+        ..LoginQuery::default()
+    }
+    .destination()
+}
+
+#[wasm_bindgen_test]
+fn login_continues_to_the_page_that_was_asked_for() {
+    assert_eq!(destination(Some("/chat")), Route::Chat);
+}
+
+#[wasm_bindgen_test]
+fn login_falls_back_to_the_chat() {
+    for next in [
+        None,
+        Some("/"),
+        Some("/logout"),
+        Some("/404"),
+        Some("/no/such/page"),
+    ] {
+        assert_eq!(destination(next), Route::Chat, "{:?}", next);
+    }
+}
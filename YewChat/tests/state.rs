@@ -0,0 +1,797 @@
+use std::collections::BTreeMap;
+
+use yewchat::services::outbox::{Delivery, OutboxEvent, Outgoing};
+use yewchat::services::websocket::{ConnectionState, ConnectionStatus};
+use yewchat::state::{
+    complete_mention, reactors, Action, ChatState, Command, Conversation, TYPING_IDLE_MS,
+    TYPING_THROTTLE_MS, TYPING_TIMEOUT_MS,
+};
+use yewchat_protocol::{
+    ClientMessage, DirectData, DirectRejectedData, EditData, MembersData, MessageData, PostData,
+    ReactionData, ReadData, ServerMessage, TypingData, LOBBY, ROOM_MAX_LEN,
+};
+
+fn state() -> ChatState {
+    ChatState::new("alice".into(), ConnectionStatus::default())
+}
+
+fn users(names: &[&str]) -> Action {
+    let names = names.iter().map(|n| n.to_string()).collect();
+    Action::Frame(ServerMessage::Users(names).encode())
+}
+
+/// A line in the lobby, not yet numbered; set the other fields by name.
+fn line(from: &str, text: &str) -> MessageData {
+    MessageData {
+        id: None,
+        room: LOBBY.into(),
+        to: None,
+        parent_id: None,
+        from: from.into(),
+        message: text.into(),
+        time: 1_700_000_000_000,
+        edited: None,
+        edits: vec![],
+        deleted: false,
+        reactions: BTreeMap::new(),
+    }
+}
+
+fn frame(msg: ServerMessage) -> Action {
+    Action::Frame(msg.encode())
+}
+
+fn post(room: &str, text: &str) -> Command {
+    Command::Send(ClientMessage::Post(PostData {
+        room: room.into(),
+        message: text.into(),
+        parent_id: None,
+    }))
+}
+
+fn texts(state: &ChatState) -> Vec<&str> {
+    state
+        .messages()
+        .iter()
+        .map(|m| m.message.as_str())
+        .collect()
+}
+
+fn queued(id: u64, text: &str) -> Action {
+    Action::Outbox(OutboxEvent::Queued(Outgoing {
+        id,
+        frame: ClientMessage::Message(text.into()).encode(),
+        queued_at: 0.0,
+    }))
+}
+
+#[test]
+fn users_frame_replaces_the_user_list() {
+    let mut state = state();
+    state.reduce(users(&["alice", "bob"]));
+    state.reduce(users(&["bob"]));
+
+    let names: Vec<_> = state.users.iter().map(|u| u.name.as_str()).collect();
+    assert_eq!(names, ["bob"]);
+    assert!(state.users[0].avatar.ends_with("/bob.svg"));
+}
+
+#[test]
+fn message_frames_are_appended_in_order() {
+    let mut state = state();
+    state.reduce(frame(ServerMessage::Message(line("bob", "satu"))));
+    state.reduce(frame(ServerMessage::Message(line("carol", "dua"))));
+
+    assert_eq!(texts(&state), ["satu", "dua"]);
+    // carol is not in the user list but still gets an avatar.
+    assert!(state.avatar("carol").ends_with("/carol.svg"));
+}
+
+#[test]
+fn garbage_frames_are_counted_and_ignored() {
+    let mut state = state();
+    state.reduce(frame(ServerMessage::Message(line("bob", "sebelum"))));
+    for frame in [
+        "",
+        "not json",
+        r#"{"messageType":"presence","data":"bob"}"#,
+        r#"{"messageType":"message"}"#,
+        r#"{"messageType":"message","data":"{\"from\":\"bob\"}"}"#,
+    ] {
+        assert!(state.reduce(Action::Frame(frame.into())).is_empty());
+    }
+    state.reduce(frame(ServerMessage::Message(line("bob", "sesudah"))));
+
+    assert_eq!(state.decode_errors, 5);
+    assert_eq!(state.messages().len(), 2);
+}
+
+#[test]
+fn submit_sends_non_empty_lines() {
+    let mut state = state();
+    assert_eq!(
+        state.reduce(Action::Submit("halo".into())),
+        [post(LOBBY, "halo")]
+    );
+    assert!(state.reduce(Action::Submit("   ".into())).is_empty());
+}
+
+#[test]
+fn own_lines_leave_the_outbox_when_echoed() {
+    let mut state = state();
+    state.reduce(queued(1, "halo"));
+    state.reduce(queued(1, "halo"));
+    assert_eq!(state.outbox.len(), 1);
+    assert_eq!(state.outbox[0].delivery, Delivery::Pending);
+
+    state.reduce(Action::Outbox(OutboxEvent::Delivery(1, Delivery::Written)));
+    assert_eq!(state.outbox[0].delivery, Delivery::Written);
+
+    // Someone else saying the same thing does not count as our echo.
+    state.reduce(frame(ServerMessage::Message(line("bob", "halo"))));
+    assert_eq!(state.outbox.len(), 1);
+
+    state.reduce(frame(ServerMessage::Message(line("alice", "halo"))));
+    assert!(state.outbox.is_empty());
+    assert_eq!(state.messages().len(), 2);
+}
+
+#[test]
+fn only_failed_lines_can_be_retried() {
+    let mut state = state();
+    state.reduce(queued(1, "halo"));
+    assert!(state.reduce(Action::Retry(1)).is_empty());
+
+    state.reduce(Action::Outbox(OutboxEvent::Delivery(1, Delivery::Failed)));
+    assert_eq!(state.reduce(Action::Retry(1)), [post(LOBBY, "halo")]);
+    assert!(state.outbox.is_empty());
+}
+
+#[test]
+fn connection_status_is_tracked() {
+    let mut state = state();
+    let status = ConnectionStatus {
+        state: ConnectionState::Reconnecting {
+            attempt: 2,
+            delay_ms: 1000,
+        },
+        last_error: Some("connection closed (code 1006)".into()),
+    };
+    state.reduce(Action::Connection(status.clone()));
+    assert_eq!(state.status, status);
+}
+
+#[test]
+fn rejections_are_kept_until_the_next_ack() {
+    let mut state = state();
+    state.reduce(Action::Frame(
+        ServerMessage::RegisterRejected("Nama ini sedang dipakai".into()).encode(),
+    ));
+    assert_eq!(state.rejected.as_deref(), Some("Nama ini sedang dipakai"));
+
+    state.reduce(Action::Frame(
+        ServerMessage::RegisterAck("alice".into()).encode(),
+    ));
+    assert_eq!(state.rejected, None);
+    assert_eq!(state.decode_errors, 0);
+}
+
+#[test]
+fn replayed_messages_are_not_shown_twice() {
+    let mut state = state();
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(1),
+        ..line("bob", "satu")
+    })));
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(2),
+        ..line("bob", "dua")
+    })));
+    // The server replays from before what we had after a resume.
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(2),
+        ..line("bob", "dua")
+    })));
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(3),
+        ..line("bob", "tiga")
+    })));
+
+    assert_eq!(texts(&state), ["satu", "dua", "tiga"]);
+}
+
+#[test]
+fn viewing_a_room_joins_it_once() {
+    let mut state = state();
+    assert_eq!(
+        state.reduce(Action::View("rust".into())),
+        [Command::Send(ClientMessage::Join("rust".into()))]
+    );
+    assert_eq!(state.room, "rust");
+    assert!(state.reduce(Action::View(LOBBY.into())).is_empty());
+    assert!(state.reduce(Action::View("rust".into())).is_empty());
+
+    state.reduce(Action::Frame(
+        ServerMessage::Members(MembersData {
+            room: "rust".into(),
+            users: vec!["alice".into(), "bob".into()],
+        })
+        .encode(),
+    ));
+    assert_eq!(state.current().members.len(), 2);
+    assert_eq!(state.room_list(), [LOBBY, "rust"]);
+}
+
+#[test]
+fn rooms_with_names_the_server_refuses_are_not_joined() {
+    let mut state = state();
+    for room in ["", "Rust", "a/b", &"x".repeat(ROOM_MAX_LEN + 1)] {
+        assert!(
+            state.reduce(Action::View(room.into())).is_empty(),
+            "{}",
+            room
+        );
+    }
+    assert_eq!(state.room, LOBBY);
+    assert_eq!(state.room_list(), [LOBBY]);
+}
+
+#[test]
+fn messages_are_kept_per_room_and_counted_until_seen() {
+    let mut state = state();
+    state.reduce(Action::View("rust".into()));
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(1),
+        room: "rust".into(),
+        ..line("bob", "di rust")
+    })));
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(2),
+        ..line("bob", "di lobby")
+    })));
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(3),
+        ..line("alice", "punyaku")
+    })));
+
+    assert_eq!(texts(&state), ["di rust"]);
+    assert_eq!(state.rooms["rust"].unread, 0);
+    // Our own lines are never unread.
+    assert_eq!(state.rooms[LOBBY].unread, 1);
+
+    state.reduce(Action::View(LOBBY.into()));
+    assert_eq!(texts(&state), ["di lobby", "punyaku"]);
+    assert_eq!(state.rooms[LOBBY].unread, 0);
+}
+
+#[test]
+fn lines_go_to_the_room_on_screen() {
+    let mut state = state();
+    state.reduce(Action::View("rust".into()));
+    assert_eq!(
+        state.reduce(Action::Submit("halo".into())),
+        [post("rust", "halo")]
+    );
+}
+
+#[test]
+fn leaving_a_room_returns_to_the_lobby() {
+    let mut state = state();
+    state.reduce(Action::View("rust".into()));
+    assert_eq!(
+        state.reduce(Action::Leave("rust".into())),
+        [Command::Send(ClientMessage::Leave("rust".into()))]
+    );
+    assert_eq!(state.room, LOBBY);
+    assert!(!state.rooms.contains_key("rust"));
+    // There is no leaving the lobby.
+    assert!(state.reduce(Action::Leave(LOBBY.into())).is_empty());
+}
+
+#[test]
+fn rooms_are_rejoined_after_a_reconnect() {
+    let mut state = state();
+    state.reduce(Action::View("rust".into()));
+    state.reduce(Action::Frame(
+        ServerMessage::Rooms(vec![LOBBY.into(), "go".into()]).encode(),
+    ));
+    assert_eq!(state.room_list(), [LOBBY, "go", "rust"]);
+
+    assert_eq!(
+        state.reduce(Action::Frame(
+            ServerMessage::RegisterAck("alice".into()).encode()
+        )),
+        [
+            Command::Send(ClientMessage::ListRooms),
+            Command::Send(ClientMessage::Join(LOBBY.into())),
+            Command::Send(ClientMessage::Join("rust".into())),
+        ]
+    );
+}
+
+#[test]
+fn direct_messages_are_kept_per_peer() {
+    let mut state = state();
+    state.reduce(frame(ServerMessage::Direct(MessageData {
+        to: Some("alice".into()),
+        ..line("bob", "psst")
+    })));
+    state.reduce(frame(ServerMessage::Direct(MessageData {
+        to: Some("carol".into()),
+        ..line("alice", "halo carol")
+    })));
+    state.reduce(frame(ServerMessage::Message(line("bob", "di lobby"))));
+
+    assert_eq!(texts(&state), ["di lobby"]);
+    assert_eq!(state.directs["bob"].unread, 1);
+    assert_eq!(state.directs["carol"].unread, 0);
+
+    state.reduce(Action::Direct("bob".into()));
+    assert_eq!(state.conversation(), Conversation::Direct("bob".into()));
+    assert_eq!(texts(&state), ["psst"]);
+    assert_eq!(state.directs["bob"].unread, 0);
+
+    // Going back to a room leaves the direct conversation.
+    state.reduce(Action::View(LOBBY.into()));
+    assert_eq!(state.direct, None);
+}
+
+#[test]
+fn lines_in_a_direct_conversation_go_to_the_peer() {
+    let mut state = state();
+    state.reduce(Action::Direct("bob".into()));
+    assert_eq!(
+        state.reduce(Action::Submit("psst".into())),
+        [Command::Send(ClientMessage::Direct(DirectData {
+            to: "bob".into(),
+            message: "psst".into(),
+            parent_id: None,
+        }))]
+    );
+
+    // Talking to yourself is not a conversation.
+    state.reduce(Action::Direct("alice".into()));
+    assert_eq!(state.direct.as_deref(), Some("bob"));
+}
+
+#[test]
+fn direct_lines_nobody_received_are_marked_failed() {
+    let mut state = state();
+    let psst = DirectData {
+        to: "bob".into(),
+        message: "psst".into(),
+        parent_id: None,
+    };
+    state.reduce(Action::Outbox(OutboxEvent::Queued(Outgoing {
+        id: 1,
+        frame: ClientMessage::Direct(psst.clone()).encode(),
+        queued_at: 0.0,
+    })));
+    state.reduce(Action::Outbox(OutboxEvent::Delivery(1, Delivery::Written)));
+
+    state.reduce(frame(ServerMessage::DirectRejected(DirectRejectedData {
+        direct: psst,
+        reason: "bob tidak sedang online".into(),
+    })));
+    assert_eq!(state.outbox[0].delivery, Delivery::Failed);
+    assert_eq!(
+        state.reduce(Action::Retry(1)),
+        [Command::Send(ClientMessage::Direct(DirectData {
+            to: "bob".into(),
+            message: "psst".into(),
+            parent_id: None,
+        }))]
+    );
+}
+
+#[test]
+fn replies_live_in_their_thread() {
+    let mut state = state();
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(1),
+        ..line("bob", "ada yang pakai yew?")
+    })));
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(2),
+        parent_id: Some(1),
+        ..line("carol", "aku")
+    })));
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(3),
+        ..line("bob", "topik lain")
+    })));
+    // Its parent is gone, so it cannot hide in a thread.
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(4),
+        parent_id: Some(99),
+        ..line("carol", "yatim")
+    })));
+
+    assert_eq!(
+        texts(&state),
+        ["ada yang pakai yew?", "topik lain", "yatim"]
+    );
+    assert_eq!(state.replies(1), 1);
+    assert_eq!(state.open_thread(), None);
+
+    state.reduce(Action::OpenThread(1));
+    let (parent, replies) = state.open_thread().unwrap();
+    assert_eq!(parent.message, "ada yang pakai yew?");
+    assert_eq!(replies.len(), 1);
+    assert_eq!(replies[0].message, "aku");
+
+    state.reduce(Action::View("rust".into()));
+    assert_eq!(state.thread, None);
+}
+
+#[test]
+fn thread_replies_name_their_parent() {
+    let mut state = state();
+    assert!(state.reduce(Action::Reply("aku".into())).is_empty());
+
+    state.reduce(Action::OpenThread(1));
+    assert_eq!(
+        state.reduce(Action::Reply("aku".into())),
+        [Command::Send(ClientMessage::Post(PostData {
+            room: LOBBY.into(),
+            message: "aku".into(),
+            parent_id: Some(1),
+        }))]
+    );
+}
+
+#[test]
+fn only_our_standing_messages_can_be_changed() {
+    let mut state = state();
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(1),
+        ..line("alice", "halo smua")
+    })));
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(2),
+        ..line("bob", "halo")
+    })));
+
+    assert_eq!(
+        state.reduce(Action::Edit(1, "halo semua".into())),
+        [Command::Send(ClientMessage::Edit(EditData {
+            id: 1,
+            message: "halo semua".into(),
+        }))]
+    );
+    // Unchanged, blank, or not ours.
+    assert!(state.reduce(Action::Edit(1, "halo smua".into())).is_empty());
+    assert!(state.reduce(Action::Edit(1, "  ".into())).is_empty());
+    assert!(state.reduce(Action::Edit(2, "dibajak".into())).is_empty());
+    assert!(state.reduce(Action::Delete(2)).is_empty());
+
+    assert_eq!(
+        state.reduce(Action::Delete(1)),
+        [Command::Send(ClientMessage::Delete(1))]
+    );
+}
+
+#[test]
+fn edits_and_deletes_update_messages_in_place() {
+    let mut state = state();
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(1),
+        ..line("bob", "halo smua")
+    })));
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(2),
+        ..line("bob", "ups")
+    })));
+
+    let mut edited = MessageData {
+        id: Some(1),
+        ..line("bob", "halo smua")
+    };
+    edited.edit("halo semua".into(), 1_700_000_060_000);
+    state.reduce(frame(ServerMessage::Edit(edited)));
+    state.reduce(frame(ServerMessage::Delete(2)));
+
+    assert_eq!(texts(&state), ["halo semua", ""]);
+    let messages = state.messages();
+    assert_eq!(messages[0].edits[0].message, "halo smua");
+    assert!(messages[0].edited.is_some());
+    assert!(messages[1].deleted);
+}
+
+fn reaction(id: u64, emoji: &str, from: Option<&str>) -> ReactionData {
+    ReactionData {
+        id,
+        emoji: emoji.into(),
+        from: from.map(Into::into),
+    }
+}
+
+#[test]
+fn reactions_toggle_our_own() {
+    let mut state = state();
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(1),
+        ..line("bob", "rilis!")
+    })));
+
+    assert_eq!(
+        state.reduce(Action::React(1, "🎉".into())),
+        [Command::Send(ClientMessage::React(reaction(1, "🎉", None)))]
+    );
+    state.reduce(frame(ServerMessage::React(reaction(
+        1,
+        "🎉",
+        Some("alice"),
+    ))));
+    assert_eq!(
+        state.reduce(Action::React(1, "🎉".into())),
+        [Command::Send(ClientMessage::Unreact(reaction(
+            1, "🎉", None
+        )))]
+    );
+    // Nothing to react to off screen or once gone.
+    assert!(state.reduce(Action::React(2, "🎉".into())).is_empty());
+    state.reduce(frame(ServerMessage::Delete(1)));
+    assert!(state.reduce(Action::React(1, "🎉".into())).is_empty());
+}
+
+#[test]
+fn reactions_are_counted_per_emoji() {
+    let mut state = state();
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(1),
+        ..line("bob", "rilis!")
+    })));
+    for (emoji, from) in [("👍", "bob"), ("👍", "carol"), ("🎉", "carol")] {
+        state.reduce(frame(ServerMessage::React(reaction(1, emoji, Some(from)))));
+    }
+    state.reduce(frame(ServerMessage::Unreact(reaction(
+        1,
+        "🎉",
+        Some("carol"),
+    ))));
+
+    let reactions = &state.messages()[0].reactions;
+    assert_eq!(reactions.len(), 1);
+    assert_eq!(reactions["👍"], ["bob", "carol"]);
+}
+
+fn online() -> ChatState {
+    let mut state = state();
+    state.reduce(Action::Connection(ConnectionStatus {
+        state: ConnectionState::Open,
+        last_error: None,
+    }));
+    state
+}
+
+fn typing(to: Option<&str>, from: Option<&str>, typing: bool) -> TypingData {
+    TypingData {
+        room: LOBBY.into(),
+        to: to.map(Into::into),
+        from: from.map(Into::into),
+        typing,
+    }
+}
+
+/// `from` typing in the lobby, or to us if `to` is set.
+fn typing_from(from: &str, to: Option<&str>, on: bool) -> Action {
+    frame(ServerMessage::Typing(typing(to, Some(from), on)))
+}
+
+fn typed(typing: TypingData) -> Command {
+    Command::Send(ClientMessage::Typing(typing))
+}
+
+#[test]
+fn typing_is_throttled_and_stops_when_idle() {
+    let mut state = online();
+    assert_eq!(
+        state.reduce(Action::Input("h".into(), 0.0)),
+        [typed(typing(None, None, true))]
+    );
+    assert!(state.reduce(Action::Input("ha".into(), 1_000.0)).is_empty());
+    assert_eq!(
+        state.reduce(Action::Input("hal".into(), TYPING_THROTTLE_MS)),
+        [typed(typing(None, None, true))]
+    );
+    assert!(state
+        .reduce(Action::Tick(TYPING_THROTTLE_MS + 1.0))
+        .is_empty());
+    assert_eq!(
+        state.reduce(Action::Tick(TYPING_THROTTLE_MS + TYPING_IDLE_MS)),
+        [typed(typing(None, None, false))]
+    );
+    assert!(state.reduce(Action::Tick(60_000.0)).is_empty());
+}
+
+#[test]
+fn typing_stops_on_send_clear_or_leaving() {
+    // Offline, nobody would hear it.
+    assert!(state().reduce(Action::Input("halo".into(), 0.0)).is_empty());
+
+    let mut state = online();
+    state.reduce(Action::Input("halo".into(), 0.0));
+    assert_eq!(
+        state.reduce(Action::Submit("halo".into())),
+        [post(LOBBY, "halo"), typed(typing(None, None, false))]
+    );
+
+    state.reduce(Action::Input("x".into(), 1.0));
+    assert_eq!(
+        state.reduce(Action::Input(" ".into(), 2.0)),
+        [typed(typing(None, None, false))]
+    );
+
+    state.reduce(Action::Direct("bob".into()));
+    state.reduce(Action::Input("psst".into(), 3.0));
+    assert_eq!(
+        state.reduce(Action::View(LOBBY.into())),
+        [typed(typing(Some("bob"), None, false))]
+    );
+}
+
+#[test]
+fn others_typing_is_shown_until_it_goes_stale() {
+    let mut state = state();
+    state.reduce(Action::Tick(0.0));
+    state.reduce(typing_from("bob", None, true));
+    state.reduce(typing_from("carol", None, true));
+    assert_eq!(state.typing(), ["bob", "carol"]);
+
+    // bob sends what they typed, carol stops.
+    state.reduce(frame(ServerMessage::Message(line("bob", "halo"))));
+    state.reduce(typing_from("carol", None, false));
+    assert!(state.typing().is_empty());
+
+    state.reduce(Action::Direct("dave".into()));
+    state.reduce(typing_from("dave", Some("alice"), true));
+    assert_eq!(state.typing(), ["dave"]);
+    state.reduce(Action::Tick(TYPING_TIMEOUT_MS));
+    assert!(state.typing().is_empty());
+}
+
+#[test]
+fn typing_alone_does_not_start_a_conversation() {
+    let mut state = state();
+    state.reduce(typing_from("dave", Some("alice"), true));
+    state.reduce(typing_from("erin", Some("alice"), false));
+    assert!(state.directs.is_empty());
+}
+
+fn read_by(from: &str, id: u64) -> Action {
+    frame(ServerMessage::Read(ReadData {
+        id,
+        from: Some(from.into()),
+    }))
+}
+
+#[test]
+fn the_newest_line_on_screen_is_reported_read_once() {
+    let mut state = online();
+    assert!(state.reduce(Action::Seen).is_empty());
+
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(1),
+        ..line("bob", "satu")
+    })));
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(2),
+        ..line("bob", "dua")
+    })));
+    // Replies are read in their thread.
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(3),
+        parent_id: Some(1),
+        ..line("bob", "balasan")
+    })));
+    let read = Command::Send(ClientMessage::Read(ReadData { id: 2, from: None }));
+    assert_eq!(state.reduce(Action::Seen), [read]);
+    assert!(state.reduce(Action::Seen).is_empty());
+}
+
+#[test]
+fn read_markers_are_sent_again_after_a_reconnect() {
+    let mut state = online();
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(1),
+        ..line("bob", "satu")
+    })));
+    let read = Command::Send(ClientMessage::Read(ReadData { id: 1, from: None }));
+    assert_eq!(state.reduce(Action::Seen), vec![read.clone()]);
+
+    state.reduce(Action::Connection(ConnectionStatus {
+        state: ConnectionState::Reconnecting {
+            attempt: 1,
+            delay_ms: 500,
+        },
+        last_error: None,
+    }));
+    // Nothing is kept to be sent later.
+    assert!(state.reduce(Action::Seen).is_empty());
+
+    state.reduce(Action::Connection(ConnectionStatus {
+        state: ConnectionState::Open,
+        last_error: None,
+    }));
+    assert_eq!(state.reduce(Action::Seen), [read]);
+}
+
+#[test]
+fn readers_show_under_the_newest_line_they_read() {
+    let mut state = state();
+    for id in 1..=3 {
+        state.reduce(frame(ServerMessage::Message(MessageData {
+            id: Some(id),
+            ..line("bob", "halo")
+        })));
+    }
+    state.reduce(read_by("bob", 3));
+    state.reduce(read_by("carol", 1));
+    state.reduce(read_by("carol", 2));
+    // Markers never move back, and ours is not shown.
+    state.reduce(read_by("carol", 1));
+    state.reduce(read_by("alice", 3));
+    // Nor those for lines we never got.
+    state.reduce(read_by("dave", 9));
+
+    let markers = state.read_markers();
+    assert_eq!(markers.len(), 2);
+    assert_eq!(markers[&2], ["carol"]);
+    assert_eq!(markers[&3], ["bob"]);
+}
+
+#[test]
+fn mentions_of_users_online_are_linked_on_send() {
+    let mut state = state();
+    state.reduce(users(&["alice", "bob", "bobby"]));
+
+    assert_eq!(
+        state.reduce(Action::Submit("@bob @carol halo".into())),
+        [post(LOBBY, "<@bob> @carol halo")]
+    );
+    assert_eq!(state.suggestions("hai @BO"), ["bob", "bobby"]);
+    assert!(state.suggestions("hai @al").is_empty());
+    assert!(state.suggestions("hai @bob ").is_empty());
+    assert_eq!(complete_mention("hai @bo", "bobby"), "hai @bobby ");
+}
+
+#[test]
+fn reaction_chips_name_who_reacted() {
+    let nicks = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+    assert_eq!(reactors(&nicks(&["bob"]), "alice"), "bob");
+    assert_eq!(reactors(&nicks(&["bob", "alice"]), "alice"), "bob, Anda");
+    assert_eq!(
+        reactors(&nicks(&["bob", "carol", "dave", "erin", "alice"]), "alice"),
+        "bob, carol, dave +2"
+    );
+}
+
+#[test]
+fn the_mentions_filter_keeps_lines_mentioning_us() {
+    let mut state = state();
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(1),
+        ..line("bob", "halo <@alice>")
+    })));
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(2),
+        ..line("bob", "halo <@carol>")
+    })));
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(3),
+        parent_id: Some(2),
+        ..line("carol", "<@alice> lihat ini")
+    })));
+    state.reduce(frame(ServerMessage::Message(MessageData {
+        id: Some(4),
+        ..line("bob", "halo @alice")
+    })));
+    assert_eq!(texts(&state).len(), 3);
+
+    state.reduce(Action::ToggleMentions);
+    let timeline: Vec<_> = state.timeline().iter().map(|m| m.id).collect();
+    assert_eq!(timeline, [Some(1), Some(3)]);
+    state.reduce(Action::ToggleMentions);
+    assert_eq!(state.timeline().len(), 3);
+}
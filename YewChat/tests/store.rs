@@ -0,0 +1,75 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use yew::Callback;
+use yewchat::services::websocket::{ConnectionState, ConnectionStatus};
+use yewchat::store::{AppState, Settings, Store, Subscription};
+
+fn store() -> Store {
+    Store::new(AppState::new(Settings {
+        server: "ws://127.0.0.1:8080".into(),
+        remember: true,
+    }))
+}
+
+/// Subscribes to `store` and returns what it has been told so far.
+fn record(store: &Store) -> (Rc<RefCell<Vec<AppState>>>, Subscription) {
+    let seen = Rc::new(RefCell::new(vec![]));
+    let subscription = store.subscribe({
+        let seen = seen.clone();
+        Callback::from(move |state| seen.borrow_mut().push(state))
+    });
+    (seen, subscription)
+}
+
+#[test]
+fn subscribers_hear_every_change() {
+    let store = store();
+    let (seen, _subscription) = record(&store);
+
+    store.update(|state| state.user = Some("alice".into()));
+    store.update(|state| state.settings.remember = false);
+
+    let seen = seen.borrow();
+    assert_eq!(seen.len(), 2);
+    assert_eq!(seen[0].user.as_deref(), Some("alice"));
+    assert!(!seen[1].settings.remember);
+    assert_eq!(seen[1], store.get());
+}
+
+#[test]
+fn updates_that_change_nothing_are_not_announced() {
+    let store = store();
+    let (seen, _subscription) = record(&store);
+
+    store.update(|state| state.connection = ConnectionStatus::default());
+    store.update(|_| {});
+    assert!(seen.borrow().is_empty());
+
+    store.update(|state| state.connection.state = ConnectionState::Open);
+    assert_eq!(seen.borrow().len(), 1);
+}
+
+#[test]
+fn dropping_the_subscription_unsubscribes() {
+    let store = store();
+    let (seen, subscription) = record(&store);
+    let (others, _other) = record(&store);
+
+    drop(subscription);
+    store.update(|state| state.user = Some("alice".into()));
+
+    assert!(seen.borrow().is_empty());
+    assert_eq!(others.borrow().len(), 1);
+}
+
+#[test]
+fn clones_share_state() {
+    let store = store();
+    let clone = store.clone();
+    clone.update(|state| state.user = Some("alice".into()));
+
+    assert_eq!(store.get().user.as_deref(), Some("alice"));
+    assert!(store == clone);
+    assert!(store != self::store());
+}
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+/// Path of the HTTP endpoint that trades credentials for an [`AuthToken`],
+/// on the same host and port as the WebSocket.
+pub const AUTH_PATH: &str = "/auth";
+/// Query parameter carrying [`AuthToken::token`] on the WebSocket handshake.
+pub const TOKEN_PARAM: &str = "token";
+/// Close code the server uses when it ends a session because its token ran
+/// out, or closes a new socket presenting one that already has.
+pub const CLOSE_TOKEN_EXPIRED: u16 = 4001;
+/// Close code the server uses right after the handshake when the token is
+/// missing or not one it issued, as after a restart with a new key. Browsers
+/// cannot see why an upgrade was refused, but they do get close codes.
+pub const CLOSE_TOKEN_REJECTED: u16 = 4002;
+
+/// Body of a `POST` to [`AUTH_PATH`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthRequest {
+    pub nick: String,
+    /// The nickname's password if it has an account, otherwise an invite
+    /// code.
+    pub secret: String,
+}
+
+/// Successful reply from [`AUTH_PATH`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthToken {
+    /// Opaque, signed by the server, valid for `nick` only.
+    pub token: String,
+    /// When the token stops being accepted, in milliseconds since the Unix
+    /// epoch.
+    pub expires_at: u64,
+}
+
+/// Failed reply from [`AUTH_PATH`]. `error` is written for users.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthError {
+    pub error: String,
+}
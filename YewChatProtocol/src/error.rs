@@ -0,0 +1,46 @@
+use std::fmt;
+
+use crate::MsgTypes;
+
+/// Why a frame could not be turned into a typed message.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The frame is not a valid envelope.
+    Json(serde_json::Error),
+    /// The envelope names a `messageType` this build does not know.
+    UnknownType(String),
+    /// The frame is valid but this side never receives that kind.
+    UnexpectedType(MsgTypes),
+    /// A field the kind needs is absent.
+    MissingField { kind: MsgTypes, field: &'static str },
+    /// The `data` payload does not match the kind.
+    Payload {
+        kind: MsgTypes,
+        source: serde_json::Error,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Json(e) => write!(f, "malformed frame: {}", e),
+            DecodeError::UnknownType(kind) => write!(f, "unknown message type `{}`", kind),
+            DecodeError::UnexpectedType(kind) => write!(f, "unexpected `{}` frame", kind),
+            DecodeError::MissingField { kind, field } => {
+                write!(f, "`{}` frame is missing `{}`", kind, field)
+            }
+            DecodeError::Payload { kind, source } => {
+                write!(f, "bad `{}` payload: {}", kind, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Json(e) | DecodeError::Payload { source: e, .. } => Some(e),
+            _ => None,
+        }
+    }
+}
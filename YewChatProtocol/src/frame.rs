@@ -0,0 +1,347 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ClientMessage, DecodeError, DirectData, DirectRejectedData, EditData, MembersData, MessageData,
+    PostData, ReactionData, ReadData, ResumeData, ServerMessage, TypingData,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MsgTypes {
+    Users,
+    Register,
+    Message,
+    RegisterAck,
+    RegisterRejected,
+    Resume,
+    ResumeToken,
+    Post,
+    Join,
+    Leave,
+    ListRooms,
+    Rooms,
+    Members,
+    Direct,
+    DirectRejected,
+    Edit,
+    Delete,
+    React,
+    Unreact,
+    Typing,
+    Read,
+}
+
+impl MsgTypes {
+    const ALL: [MsgTypes; 21] = [
+        MsgTypes::Users,
+        MsgTypes::Register,
+        MsgTypes::Message,
+        MsgTypes::RegisterAck,
+        MsgTypes::RegisterRejected,
+        MsgTypes::Resume,
+        MsgTypes::ResumeToken,
+        MsgTypes::Post,
+        MsgTypes::Join,
+        MsgTypes::Leave,
+        MsgTypes::ListRooms,
+        MsgTypes::Rooms,
+        MsgTypes::Members,
+        MsgTypes::Direct,
+        MsgTypes::DirectRejected,
+        MsgTypes::Edit,
+        MsgTypes::Delete,
+        MsgTypes::React,
+        MsgTypes::Unreact,
+        MsgTypes::Typing,
+        MsgTypes::Read,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MsgTypes::Users => "users",
+            MsgTypes::Register => "register",
+            MsgTypes::Message => "message",
+            MsgTypes::RegisterAck => "registerAck",
+            MsgTypes::RegisterRejected => "registerRejected",
+            MsgTypes::Resume => "resume",
+            MsgTypes::ResumeToken => "resumeToken",
+            MsgTypes::Post => "post",
+            MsgTypes::Join => "join",
+            MsgTypes::Leave => "leave",
+            MsgTypes::ListRooms => "listRooms",
+            MsgTypes::Rooms => "rooms",
+            MsgTypes::Members => "members",
+            MsgTypes::Direct => "direct",
+            MsgTypes::DirectRejected => "directRejected",
+            MsgTypes::Edit => "edit",
+            MsgTypes::Delete => "delete",
+            MsgTypes::React => "react",
+            MsgTypes::Unreact => "unreact",
+            MsgTypes::Typing => "typing",
+            MsgTypes::Read => "read",
+        }
+    }
+}
+
+impl fmt::Display for MsgTypes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MsgTypes {
+    type Err = DecodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s)
+            .ok_or_else(|| DecodeError::UnknownType(s.to_owned()))
+    }
+}
+
+/// Same shape as [`WebSocketMessage`] but with the kind left as a plain
+/// string, so frames from newer servers can be told apart from garbage.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawFrame {
+    message_type: String,
+    #[serde(default)]
+    data: Option<String>,
+    #[serde(default)]
+    data_array: Option<Vec<String>>,
+}
+
+/// The untyped envelope every frame travels in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebSocketMessage {
+    pub message_type: MsgTypes,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_array: Option<Vec<String>>,
+}
+
+impl WebSocketMessage {
+    fn new(message_type: MsgTypes) -> Self {
+        Self {
+            message_type,
+            data: None,
+            data_array: None,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).expect("envelope always serializes")
+    }
+
+    /// Parses an envelope, reporting kinds this crate does not know about as
+    /// [`DecodeError::UnknownType`] rather than as malformed JSON.
+    pub fn decode(s: &str) -> Result<Self, DecodeError> {
+        let raw: RawFrame = serde_json::from_str(s).map_err(DecodeError::Json)?;
+        Ok(Self {
+            message_type: raw.message_type.parse()?,
+            data: raw.data,
+            data_array: raw.data_array,
+        })
+    }
+
+    fn data(self) -> Result<String, DecodeError> {
+        let kind = self.message_type;
+        self.data.ok_or(DecodeError::MissingField {
+            kind,
+            field: "data",
+        })
+    }
+
+    fn payload<T: for<'de> Deserialize<'de>>(self) -> Result<T, DecodeError> {
+        let kind = self.message_type;
+        serde_json::from_str(&self.data()?).map_err(|source| DecodeError::Payload { kind, source })
+    }
+}
+
+impl From<ClientMessage> for WebSocketMessage {
+    fn from(msg: ClientMessage) -> Self {
+        match msg {
+            ClientMessage::Register(nick) => Self {
+                data: Some(nick),
+                ..Self::new(MsgTypes::Register)
+            },
+            ClientMessage::Message(text) => Self {
+                data: Some(text),
+                ..Self::new(MsgTypes::Message)
+            },
+            ClientMessage::Resume(resume) => Self {
+                data: Some(encode_payload(&resume)),
+                ..Self::new(MsgTypes::Resume)
+            },
+            ClientMessage::Post(post) => Self {
+                data: Some(encode_payload(&post)),
+                ..Self::new(MsgTypes::Post)
+            },
+            ClientMessage::Join(room) => Self {
+                data: Some(room),
+                ..Self::new(MsgTypes::Join)
+            },
+            ClientMessage::Leave(room) => Self {
+                data: Some(room),
+                ..Self::new(MsgTypes::Leave)
+            },
+            ClientMessage::ListRooms => Self::new(MsgTypes::ListRooms),
+            ClientMessage::Direct(direct) => Self {
+                data: Some(encode_payload(&direct)),
+                ..Self::new(MsgTypes::Direct)
+            },
+            ClientMessage::Edit(edit) => Self {
+                data: Some(encode_payload(&edit)),
+                ..Self::new(MsgTypes::Edit)
+            },
+            ClientMessage::Delete(id) => Self {
+                data: Some(id.to_string()),
+                ..Self::new(MsgTypes::Delete)
+            },
+            ClientMessage::React(reaction) => Self {
+                data: Some(encode_payload(&reaction)),
+                ..Self::new(MsgTypes::React)
+            },
+            ClientMessage::Unreact(reaction) => Self {
+                data: Some(encode_payload(&reaction)),
+                ..Self::new(MsgTypes::Unreact)
+            },
+            ClientMessage::Typing(typing) => Self {
+                data: Some(encode_payload(&typing)),
+                ..Self::new(MsgTypes::Typing)
+            },
+            ClientMessage::Read(read) => Self {
+                data: Some(encode_payload(&read)),
+                ..Self::new(MsgTypes::Read)
+            },
+        }
+    }
+}
+
+impl TryFrom<WebSocketMessage> for ClientMessage {
+    type Error = DecodeError;
+
+    fn try_from(frame: WebSocketMessage) -> Result<Self, Self::Error> {
+        match frame.message_type {
+            MsgTypes::Register => Ok(ClientMessage::Register(frame.data()?)),
+            MsgTypes::Message => Ok(ClientMessage::Message(frame.data()?)),
+            MsgTypes::Resume => Ok(ClientMessage::Resume(frame.payload::<ResumeData>()?)),
+            MsgTypes::Post => Ok(ClientMessage::Post(frame.payload::<PostData>()?)),
+            MsgTypes::Join => Ok(ClientMessage::Join(frame.data()?)),
+            MsgTypes::Leave => Ok(ClientMessage::Leave(frame.data()?)),
+            MsgTypes::ListRooms => Ok(ClientMessage::ListRooms),
+            MsgTypes::Direct => Ok(ClientMessage::Direct(frame.payload::<DirectData>()?)),
+            MsgTypes::Edit => Ok(ClientMessage::Edit(frame.payload::<EditData>()?)),
+            MsgTypes::Delete => Ok(ClientMessage::Delete(frame.payload::<u64>()?)),
+            MsgTypes::React => Ok(ClientMessage::React(frame.payload::<ReactionData>()?)),
+            MsgTypes::Unreact => Ok(ClientMessage::Unreact(frame.payload::<ReactionData>()?)),
+            MsgTypes::Typing => Ok(ClientMessage::Typing(frame.payload::<TypingData>()?)),
+            MsgTypes::Read => Ok(ClientMessage::Read(frame.payload::<ReadData>()?)),
+            kind => Err(DecodeError::UnexpectedType(kind)),
+        }
+    }
+}
+
+impl From<ServerMessage> for WebSocketMessage {
+    fn from(msg: ServerMessage) -> Self {
+        match msg {
+            ServerMessage::Users(users) => Self {
+                data_array: Some(users),
+                ..Self::new(MsgTypes::Users)
+            },
+            ServerMessage::Rooms(rooms) => Self {
+                data_array: Some(rooms),
+                ..Self::new(MsgTypes::Rooms)
+            },
+            ServerMessage::Members(members) => Self {
+                data: Some(encode_payload(&members)),
+                ..Self::new(MsgTypes::Members)
+            },
+            ServerMessage::Message(data) => Self {
+                data: Some(encode_payload(&data)),
+                ..Self::new(MsgTypes::Message)
+            },
+            ServerMessage::Direct(data) => Self {
+                data: Some(encode_payload(&data)),
+                ..Self::new(MsgTypes::Direct)
+            },
+            ServerMessage::DirectRejected(rejected) => Self {
+                data: Some(encode_payload(&rejected)),
+                ..Self::new(MsgTypes::DirectRejected)
+            },
+            ServerMessage::Edit(data) => Self {
+                data: Some(encode_payload(&data)),
+                ..Self::new(MsgTypes::Edit)
+            },
+            ServerMessage::Delete(id) => Self {
+                data: Some(id.to_string()),
+                ..Self::new(MsgTypes::Delete)
+            },
+            ServerMessage::React(reaction) => Self {
+                data: Some(encode_payload(&reaction)),
+                ..Self::new(MsgTypes::React)
+            },
+            ServerMessage::Unreact(reaction) => Self {
+                data: Some(encode_payload(&reaction)),
+                ..Self::new(MsgTypes::Unreact)
+            },
+            ServerMessage::Typing(typing) => Self {
+                data: Some(encode_payload(&typing)),
+                ..Self::new(MsgTypes::Typing)
+            },
+            ServerMessage::Read(read) => Self {
+                data: Some(encode_payload(&read)),
+                ..Self::new(MsgTypes::Read)
+            },
+            ServerMessage::RegisterAck(nick) => Self {
+                data: Some(nick),
+                ..Self::new(MsgTypes::RegisterAck)
+            },
+            ServerMessage::RegisterRejected(reason) => Self {
+                data: Some(reason),
+                ..Self::new(MsgTypes::RegisterRejected)
+            },
+            ServerMessage::ResumeToken(token) => Self {
+                data: Some(token),
+                ..Self::new(MsgTypes::ResumeToken)
+            },
+        }
+    }
+}
+
+impl TryFrom<WebSocketMessage> for ServerMessage {
+    type Error = DecodeError;
+
+    fn try_from(frame: WebSocketMessage) -> Result<Self, Self::Error> {
+        match frame.message_type {
+            MsgTypes::Users => Ok(ServerMessage::Users(frame.data_array.unwrap_or_default())),
+            MsgTypes::Rooms => Ok(ServerMessage::Rooms(frame.data_array.unwrap_or_default())),
+            MsgTypes::Members => Ok(ServerMessage::Members(frame.payload::<MembersData>()?)),
+            MsgTypes::Message => Ok(ServerMessage::Message(frame.payload::<MessageData>()?)),
+            MsgTypes::Direct => Ok(ServerMessage::Direct(frame.payload::<MessageData>()?)),
+            MsgTypes::DirectRejected => Ok(ServerMessage::DirectRejected(
+                frame.payload::<DirectRejectedData>()?,
+            )),
+            MsgTypes::Edit => Ok(ServerMessage::Edit(frame.payload::<MessageData>()?)),
+            MsgTypes::Delete => Ok(ServerMessage::Delete(frame.payload::<u64>()?)),
+            MsgTypes::React => Ok(ServerMessage::React(frame.payload::<ReactionData>()?)),
+            MsgTypes::Unreact => Ok(ServerMessage::Unreact(frame.payload::<ReactionData>()?)),
+            MsgTypes::Typing => Ok(ServerMessage::Typing(frame.payload::<TypingData>()?)),
+            MsgTypes::Read => Ok(ServerMessage::Read(frame.payload::<ReadData>()?)),
+            MsgTypes::RegisterAck => Ok(ServerMessage::RegisterAck(frame.data()?)),
+            MsgTypes::RegisterRejected => Ok(ServerMessage::RegisterRejected(frame.data()?)),
+            MsgTypes::ResumeToken => Ok(ServerMessage::ResumeToken(frame.data()?)),
+            kind => Err(DecodeError::UnexpectedType(kind)),
+        }
+    }
+}
+
+fn encode_payload<T: Serialize>(payload: &T) -> String {
+    serde_json::to_string(payload).expect("payload always serializes")
+}
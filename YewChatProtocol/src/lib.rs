@@ -0,0 +1,368 @@
+//! Wire protocol spoken between the YewChat client and its WebSocket server.
+//!
+//! Every frame on the socket is a JSON envelope (see [`WebSocketMessage`]) whose
+//! `messageType` selects how `data` and `dataArray` are interpreted. Payloads
+//! that are objects, like [`MessageData`], travel JSON-encoded inside `data`;
+//! that detail stays in this crate and callers only ever see the typed
+//! [`ClientMessage`] and [`ServerMessage`] enums.
+//!
+//! Before opening the socket the client trades a nickname and secret for an
+//! [`AuthToken`] over plain HTTP; see [`AUTH_PATH`].
+//!
+//! Chat happens in rooms. Everyone starts out in [`LOBBY`] and may `join`
+//! and `leave` others, which are created on first join and go away once
+//! empty; messages only reach the members of their room. A `direct`
+//! message goes to one user instead, wherever they are.
+//!
+//! A registered client also gets a resume token. Presenting it with
+//! [`ClientMessage::Resume`] after a dropped connection restores the same
+//! identity and replays the messages missed in between, by [`MessageData::id`].
+//!
+//! The same ids thread conversations: a message with a `parentId` is a reply
+//! to the message with that id, in the same room or direct conversation.
+//! Authors may also `edit` and `delete` their messages by id, and anyone who
+//! can see a message may `react` to it with one of [`REACTIONS`]. Everyone
+//! who can see the message hears about it, and replays carry the latest
+//! version, also of older messages that changed while the client was away.
+//!
+//! While composing, clients send `typing` every so often and once more when
+//! they stop; the others in the conversation get it with the sender filled
+//! in. Nothing is sent when a client vanishes mid-sentence, so receivers
+//! should let an indicator lapse when it is not refreshed.
+//!
+//! Clients also `read` the newest message they have shown. The server keeps
+//! how far each user has read in every conversation, tells the others there
+//! whenever it moves on and repeats it to whoever (re)connects.
+//!
+//! Mentions need nothing from the server: they are `<@nick>` tokens inside
+//! the message text, made and read with the functions in this crate.
+
+mod auth;
+mod error;
+mod frame;
+mod mention;
+mod nick;
+mod room;
+
+pub use auth::{
+    AuthError, AuthRequest, AuthToken, AUTH_PATH, CLOSE_TOKEN_EXPIRED, CLOSE_TOKEN_REJECTED,
+    TOKEN_PARAM,
+};
+pub use error::DecodeError;
+pub use frame::{MsgTypes, WebSocketMessage};
+pub use mention::{link_mentions, mention, mentions, segments, unlink_mentions, Segment};
+pub use nick::{validate_nick, NickError, NICK_MAX_LEN, NICK_MIN_LEN, RESERVED_NICKS};
+pub use room::{validate_room, RoomError, LOBBY, ROOM_MAX_LEN};
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// The emoji messages can be reacted with.
+pub const REACTIONS: [&str; 6] = ["👍", "❤️", "😂", "😮", "😢", "🎉"];
+
+/// A chat line as broadcast by the server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageData {
+    /// Assigned by the server in increasing order; `None` from servers that
+    /// do not number messages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    /// Left out on the wire for the lobby, as older servers have no rooms.
+    #[serde(default = "lobby", skip_serializing_if = "is_lobby")]
+    pub room: String,
+    /// The recipient of a direct message; `None` for one posted to `room`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// The message this one replies to in a thread. Threads are one level
+    /// deep, so the parent never has a parent itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<u64>,
+    pub from: String,
+    /// Empty once deleted.
+    pub message: String,
+    /// When the server relayed the message, in milliseconds since the Unix epoch.
+    pub time: u64,
+    /// When `message` was last changed, if it ever was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited: Option<u64>,
+    /// Earlier versions of `message`, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub edits: Vec<Revision>,
+    /// Whether the author deleted the message, leaving only a tombstone.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deleted: bool,
+    /// Who reacted with each emoji, in the order they did.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub reactions: BTreeMap<String, Vec<String>>,
+}
+
+/// A version of a message that has since been edited.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Revision {
+    pub message: String,
+    /// When this version was written, in milliseconds since the Unix epoch.
+    pub time: u64,
+}
+
+/// Frames the client sends to the server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "WebSocketMessage", into = "WebSocketMessage")]
+pub enum ClientMessage {
+    /// Announce the nickname of this connection. The server answers with
+    /// [`ServerMessage::RegisterAck`] or [`ServerMessage::RegisterRejected`].
+    Register(String),
+    /// Post a chat line to the lobby.
+    Message(String),
+    /// Post a chat line to a room we are in.
+    Post(PostData),
+    /// Enter a room, creating it if needed. Answered with
+    /// [`ServerMessage::Members`].
+    Join(String),
+    Leave(String),
+    /// Ask for [`ServerMessage::Rooms`].
+    ListRooms,
+    /// Send a chat line to one user only. It comes back to both ends as
+    /// [`ServerMessage::Direct`], or to us alone as
+    /// [`ServerMessage::DirectRejected`] if nobody by that name is online.
+    Direct(DirectData),
+    /// Change the text of one of our messages. Answered to everyone who can
+    /// see it with [`ServerMessage::Edit`].
+    Edit(EditData),
+    /// Delete one of our messages, by id. Answered to everyone who can see
+    /// it with [`ServerMessage::Delete`].
+    Delete(u64),
+    /// React to a message. Answered to everyone who can see it with
+    /// [`ServerMessage::React`].
+    React(ReactionData),
+    /// Take back a reaction. Answered to everyone who can see the message
+    /// with [`ServerMessage::Unreact`].
+    Unreact(ReactionData),
+    /// We started or stopped typing. Passed on to the others in the
+    /// conversation; never answered.
+    Typing(TypingData),
+    /// We have shown everything up to a message. Answered to everyone who
+    /// can see it with [`ServerMessage::Read`], unless we had read further.
+    Read(ReadData),
+    /// Instead of `register` on a reconnect: take back the identity from an
+    /// earlier connection. Answered like `register`.
+    Resume(ResumeData),
+}
+
+/// Payload of [`ClientMessage::Post`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostData {
+    pub room: String,
+    pub message: String,
+    /// Set to reply in the thread under that message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<u64>,
+}
+
+/// Payload of [`ClientMessage::Direct`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectData {
+    pub to: String,
+    pub message: String,
+    /// As for [`PostData::parent_id`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<u64>,
+}
+
+/// Payload of [`ServerMessage::DirectRejected`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirectRejectedData {
+    /// The message as it was sent.
+    #[serde(flatten)]
+    pub direct: DirectData,
+    /// Why it went nowhere, shown to users.
+    pub reason: String,
+}
+
+/// Payload of [`ClientMessage::Edit`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditData {
+    pub id: u64,
+    pub message: String,
+}
+
+/// Payload of the `react` and `unreact` frames.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReactionData {
+    pub id: u64,
+    /// One of [`REACTIONS`].
+    pub emoji: String,
+    /// Who reacted; filled in by the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+}
+
+/// Payload of the `typing` frames.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypingData {
+    /// Left out on the wire for the lobby, like [`MessageData::room`].
+    #[serde(default = "lobby", skip_serializing_if = "is_lobby")]
+    pub room: String,
+    /// Who is typed to in private; `None` when typing in `room`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Who is typing; filled in by the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// `false` once they stopped.
+    pub typing: bool,
+}
+
+/// Payload of the `read` frames.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadData {
+    /// The newest message read in its conversation.
+    pub id: u64,
+    /// Who read it; filled in by the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+}
+
+/// Payload of [`ClientMessage::Resume`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumeData {
+    /// From the last [`ServerMessage::ResumeToken`].
+    pub token: String,
+    /// Id of the newest message received; later ones are replayed.
+    #[serde(default)]
+    pub last_seen: Option<u64>,
+}
+
+/// Frames the server sends to the client.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "WebSocketMessage", into = "WebSocketMessage")]
+pub enum ServerMessage {
+    /// The full list of nicknames currently online.
+    Users(Vec<String>),
+    /// Every room that exists, sent on request and whenever one comes or
+    /// goes.
+    Rooms(Vec<String>),
+    /// Who is in a room we are in, sent on join and whenever it changes.
+    Members(MembersData),
+    /// A chat line from one of the users.
+    Message(MessageData),
+    /// A chat line sent to us or by us privately, with
+    /// [`MessageData::to`] set.
+    Direct(MessageData),
+    /// A [`ClientMessage::Direct`] of ours was not delivered to anyone.
+    DirectRejected(DirectRejectedData),
+    /// A message we have seen was edited; this is its new version. Also sent
+    /// on resume for messages that changed in any way while we were away.
+    Edit(MessageData),
+    /// A message we have seen was deleted, by id.
+    Delete(u64),
+    /// Someone reacted to a message we have seen.
+    React(ReactionData),
+    /// Someone took back their reaction to a message we have seen.
+    Unreact(ReactionData),
+    /// Someone in one of our conversations started or stopped typing.
+    Typing(TypingData),
+    /// Someone has read up to a message we have seen.
+    Read(ReadData),
+    /// The nickname from `register` was accepted, as registered.
+    RegisterAck(String),
+    /// The nickname from `register` was refused, and why.
+    RegisterRejected(String),
+    /// Follows [`ServerMessage::RegisterAck`]: the token to present in
+    /// [`ClientMessage::Resume`] if the connection drops.
+    ResumeToken(String),
+}
+
+/// Payload of [`ServerMessage::Members`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MembersData {
+    pub room: String,
+    /// In the order they joined.
+    pub users: Vec<String>,
+}
+
+fn lobby() -> String {
+    LOBBY.to_owned()
+}
+
+fn is_lobby(room: &str) -> bool {
+    room == LOBBY
+}
+
+impl MessageData {
+    /// Replaces the text at `now`, keeping the current one in `edits`.
+    pub fn edit(&mut self, message: String, now: u64) {
+        let old = std::mem::replace(&mut self.message, message);
+        self.edits.push(Revision {
+            message: old,
+            time: self.edited.unwrap_or(self.time),
+        });
+        self.edited = Some(now);
+    }
+
+    /// Leaves only a tombstone: no text, no history, no reactions.
+    pub fn delete(&mut self) {
+        self.message.clear();
+        self.edited = None;
+        self.edits.clear();
+        self.reactions.clear();
+        self.deleted = true;
+    }
+
+    /// Adds `nick` to those who reacted with `emoji`, returning whether they
+    /// had not already.
+    pub fn react(&mut self, emoji: &str, nick: &str) -> bool {
+        let nicks = self.reactions.entry(emoji.to_owned()).or_default();
+        if nicks.iter().any(|n| n == nick) {
+            return false;
+        }
+        nicks.push(nick.to_owned());
+        true
+    }
+
+    /// Takes `nick` out of those who reacted with `emoji`, returning whether
+    /// they were in.
+    pub fn unreact(&mut self, emoji: &str, nick: &str) -> bool {
+        let Some(nicks) = self.reactions.get_mut(emoji) else {
+            return false;
+        };
+        let before = nicks.len();
+        nicks.retain(|n| n != nick);
+        let removed = nicks.len() < before;
+        if nicks.is_empty() {
+            self.reactions.remove(emoji);
+        }
+        removed
+    }
+
+    /// Whether `nick` reacted with `emoji`.
+    pub fn reacted(&self, emoji: &str, nick: &str) -> bool {
+        self.reactions
+            .get(emoji)
+            .is_some_and(|nicks| nicks.iter().any(|n| n == nick))
+    }
+}
+
+impl ClientMessage {
+    pub fn encode(&self) -> String {
+        WebSocketMessage::from(self.clone()).encode()
+    }
+
+    pub fn decode(s: &str) -> Result<Self, DecodeError> {
+        WebSocketMessage::decode(s)?.try_into()
+    }
+}
+
+impl ServerMessage {
+    pub fn encode(&self) -> String {
+        WebSocketMessage::from(self.clone()).encode()
+    }
+
+    pub fn decode(s: &str) -> Result<Self, DecodeError> {
+        WebSocketMessage::decode(s)?.try_into()
+    }
+}
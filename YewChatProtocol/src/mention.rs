@@ -0,0 +1,99 @@
+use crate::nick::validate_nick;
+
+/// A piece of message text, as split by [`segments`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    /// A mention of the nickname.
+    Mention(&'a str),
+}
+
+/// The token that mentions `nick` in message text. Clients that know nothing
+/// of mentions show it as it is, which still reads fine.
+pub fn mention(nick: &str) -> String {
+    format!("<@{}>", nick)
+}
+
+/// Splits `text` into plain text and mentions. Anything that looks like a
+/// token without a valid nickname in it stays text.
+pub fn segments(text: &str) -> Vec<Segment<'_>> {
+    let mut segments = vec![];
+    let (mut plain, mut at) = (0, 0);
+    while let Some(found) = text[at..].find("<@") {
+        let open = at + found;
+        let inner = &text[open + 2..];
+        match inner.find('>').map(|end| &inner[..end]) {
+            Some(nick) if validate_nick(nick).is_ok() => {
+                if plain < open {
+                    segments.push(Segment::Text(&text[plain..open]));
+                }
+                segments.push(Segment::Mention(nick));
+                at = open + 2 + nick.len() + 1;
+                plain = at;
+            }
+            _ => at = open + 2,
+        }
+    }
+    if plain < text.len() {
+        segments.push(Segment::Text(&text[plain..]));
+    }
+    segments
+}
+
+/// The nicknames mentioned in `text`, in order.
+pub fn mentions(text: &str) -> Vec<&str> {
+    segments(text)
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Mention(nick) => Some(nick),
+            Segment::Text(_) => None,
+        })
+        .collect()
+}
+
+/// Turns every `@nick` in what the user typed into a mention token, for the
+/// nicknames `known` accepts. A full stop right after the nickname ends the
+/// sentence rather than the name, unless the name is known with it.
+pub fn link_mentions(text: &str, known: impl Fn(&str) -> bool) -> String {
+    let mut linked = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut word_start = true;
+    while let Some(c) = rest.chars().next() {
+        if c == '@' && word_start {
+            let after = &rest[1..];
+            let end = after
+                .find(|c: char| !is_nick_char(c))
+                .unwrap_or(after.len());
+            let word = &after[..end];
+            let nick = [word, word.trim_end_matches('.')]
+                .into_iter()
+                .find(|nick| !nick.is_empty() && known(nick));
+            if let Some(nick) = nick {
+                linked.push_str(&mention(nick));
+                rest = &after[nick.len()..];
+                word_start = false;
+                continue;
+            }
+        }
+        linked.push(c);
+        word_start = c.is_whitespace();
+        rest = &rest[c.len_utf8()..];
+    }
+    linked
+}
+
+/// `text` with its mentions written back as `@nick`, the way they were
+/// typed.
+pub fn unlink_mentions(text: &str) -> String {
+    segments(text)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => text.to_owned(),
+            Segment::Mention(nick) => format!("@{}", nick),
+        })
+        .collect()
+}
+
+fn is_nick_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
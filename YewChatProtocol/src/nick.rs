@@ -0,0 +1,64 @@
+use std::fmt;
+
+/// Shortest nickname the server accepts, in characters.
+pub const NICK_MIN_LEN: usize = 2;
+/// Longest nickname the server accepts, in characters.
+pub const NICK_MAX_LEN: usize = 20;
+/// Nicknames nobody may register, compared case-insensitively.
+pub const RESERVED_NICKS: &[&str] = &["admin", "moderator", "server", "system", "yewchat"];
+
+/// Why a nickname was refused.
+///
+/// The `Display` text is what the server sends in `registerRejected` and what
+/// `Login` shows, so it is written for users, in the UI's language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NickError {
+    TooShort,
+    TooLong,
+    /// The nickname contains something other than letters, digits, `_`, `-`
+    /// or `.`.
+    BadCharacter(char),
+    Reserved,
+    /// Someone else is online under this nickname. Only the server can tell.
+    Taken,
+}
+
+impl fmt::Display for NickError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NickError::TooShort => write!(f, "Nama minimal {} karakter", NICK_MIN_LEN),
+            NickError::TooLong => write!(f, "Nama maksimal {} karakter", NICK_MAX_LEN),
+            NickError::BadCharacter(c) => write!(
+                f,
+                "Karakter {:?} tidak boleh dipakai; gunakan huruf, angka, _, - atau .",
+                c
+            ),
+            NickError::Reserved => f.write_str("Nama ini dicadangkan"),
+            NickError::Taken => f.write_str("Nama ini sedang dipakai"),
+        }
+    }
+}
+
+impl std::error::Error for NickError {}
+
+/// Checks `nick` against the rules every server enforces. Whether it is
+/// [`NickError::Taken`] is left to the server.
+pub fn validate_nick(nick: &str) -> Result<(), NickError> {
+    let len = nick.chars().count();
+    if len < NICK_MIN_LEN {
+        return Err(NickError::TooShort);
+    }
+    if len > NICK_MAX_LEN {
+        return Err(NickError::TooLong);
+    }
+    if let Some(c) = nick
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')))
+    {
+        return Err(NickError::BadCharacter(c));
+    }
+    if RESERVED_NICKS.iter().any(|r| r.eq_ignore_ascii_case(nick)) {
+        return Err(NickError::Reserved);
+    }
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use std::fmt;
+
+/// The room everyone is in after registering. It always exists and cannot be
+/// left.
+pub const LOBBY: &str = "lobby";
+/// Longest room name the server accepts, in characters.
+pub const ROOM_MAX_LEN: usize = 32;
+
+/// Why a room name was refused. The `Display` text is shown to users.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoomError {
+    Empty,
+    TooLong,
+    /// The name contains something other than lowercase letters, digits, `_`
+    /// or `-`.
+    BadCharacter(char),
+}
+
+impl fmt::Display for RoomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoomError::Empty => f.write_str("Nama ruang tidak boleh kosong"),
+            RoomError::TooLong => write!(f, "Nama ruang maksimal {} karakter", ROOM_MAX_LEN),
+            RoomError::BadCharacter(c) => write!(
+                f,
+                "Karakter {:?} tidak boleh dipakai; gunakan huruf kecil, angka, _ atau -",
+                c
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RoomError {}
+
+/// Checks `room` against the rules every server enforces. Names are used in
+/// URLs as they are, so they are kept lowercase and free of punctuation.
+pub fn validate_room(room: &str) -> Result<(), RoomError> {
+    if room.is_empty() {
+        return Err(RoomError::Empty);
+    }
+    if room.chars().count() > ROOM_MAX_LEN {
+        return Err(RoomError::TooLong);
+    }
+    if let Some(c) = room
+        .chars()
+        .find(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-')))
+    {
+        return Err(RoomError::BadCharacter(c));
+    }
+    Ok(())
+}
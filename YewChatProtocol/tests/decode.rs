@@ -0,0 +1,72 @@
+//! Garbage and unexpected frames must come back as typed errors, never panics.
+
+use yewchat_protocol::{DecodeError, MsgTypes, ServerMessage};
+
+fn decode_err(frame: &str) -> DecodeError {
+    ServerMessage::decode(frame).expect_err(frame)
+}
+
+#[test]
+fn malformed_json_is_a_json_error() {
+    for frame in ["", "not json", "{", "[1,2,3]", "null", r#"{"data":"x"}"#] {
+        assert!(
+            matches!(decode_err(frame), DecodeError::Json(_)),
+            "{}",
+            frame
+        );
+    }
+}
+
+#[test]
+fn unknown_message_type_is_reported_by_name() {
+    match decode_err(r#"{"messageType":"presence","data":"alice"}"#) {
+        DecodeError::UnknownType(kind) => assert_eq!(kind, "presence"),
+        e => panic!("unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn message_without_data_is_a_missing_field() {
+    assert!(matches!(
+        decode_err(r#"{"messageType":"message"}"#),
+        DecodeError::MissingField {
+            kind: MsgTypes::Message,
+            field: "data"
+        }
+    ));
+}
+
+#[test]
+fn message_with_bad_payload_is_a_payload_error() {
+    for frame in [
+        r#"{"messageType":"message","data":"halo"}"#,
+        r#"{"messageType":"message","data":"{\"from\":\"alice\"}"}"#,
+        r#"{"messageType":"message","data":"{\"from\":1,\"message\":\"x\"}"}"#,
+    ] {
+        assert!(
+            matches!(
+                decode_err(frame),
+                DecodeError::Payload {
+                    kind: MsgTypes::Message,
+                    ..
+                }
+            ),
+            "{}",
+            frame
+        );
+    }
+}
+
+#[test]
+fn client_only_kind_is_unexpected() {
+    assert!(matches!(
+        decode_err(r#"{"messageType":"register","data":"alice"}"#),
+        DecodeError::UnexpectedType(MsgTypes::Register)
+    ));
+}
+
+#[test]
+fn errors_render_for_logging() {
+    let e = decode_err(r#"{"messageType":"presence"}"#);
+    assert_eq!(e.to_string(), "unknown message type `presence`");
+}
@@ -0,0 +1,44 @@
+use yewchat_protocol::{link_mentions, mention, mentions, segments, unlink_mentions, Segment};
+
+#[test]
+fn tokens_split_out_of_the_text() {
+    let text = format!("halo {} dan {}!", mention("bob"), mention("carol"));
+    assert_eq!(
+        segments(&text),
+        [
+            Segment::Text("halo "),
+            Segment::Mention("bob"),
+            Segment::Text(" dan "),
+            Segment::Mention("carol"),
+            Segment::Text("!"),
+        ]
+    );
+    assert_eq!(mentions(&text), ["bob", "carol"]);
+    assert_eq!(unlink_mentions(&text), "halo @bob dan @carol!");
+}
+
+#[test]
+fn malformed_tokens_stay_text() {
+    for text in ["<@>", "<@a b>", "<@bob", "a <@ b> c", "<@x>"] {
+        assert_eq!(segments(text), [Segment::Text(text)], "{}", text);
+    }
+    assert_eq!(
+        segments("<@<@bob>"),
+        [Segment::Text("<@"), Segment::Mention("bob")]
+    );
+}
+
+#[test]
+fn known_nicks_are_linked_as_typed() {
+    let known = |nick: &str| ["bob", "carol", "d.j"].contains(&nick);
+    assert_eq!(
+        link_mentions("@bob, tanya @carol. @dave juga", known),
+        "<@bob>, tanya <@carol>. @dave juga"
+    );
+    assert_eq!(link_mentions("@d.j.", known), "<@d.j>.");
+    // Only at the start of a word, and only whole nicknames.
+    assert_eq!(
+        link_mentions("surel@bob @bobby @bob", known),
+        "surel@bob @bobby <@bob>"
+    );
+}
@@ -0,0 +1,38 @@
+use yewchat_protocol::{validate_nick, NickError, NICK_MAX_LEN};
+
+#[test]
+fn ordinary_nicks_are_accepted() {
+    for nick in ["al", "alice", "Budi_99", "c.d-e", &"x".repeat(NICK_MAX_LEN)] {
+        assert_eq!(validate_nick(nick), Ok(()), "{}", nick);
+    }
+}
+
+#[test]
+fn length_is_counted_in_characters() {
+    assert_eq!(validate_nick(""), Err(NickError::TooShort));
+    assert_eq!(validate_nick("a"), Err(NickError::TooShort));
+    assert_eq!(
+        validate_nick(&"x".repeat(NICK_MAX_LEN + 1)),
+        Err(NickError::TooLong)
+    );
+}
+
+#[test]
+fn only_letters_digits_and_a_few_marks_are_allowed() {
+    assert_eq!(validate_nick("al ice"), Err(NickError::BadCharacter(' ')));
+    assert_eq!(validate_nick("<b>"), Err(NickError::BadCharacter('<')));
+    assert_eq!(validate_nick("andré"), Err(NickError::BadCharacter('é')));
+}
+
+#[test]
+fn reserved_nicks_are_refused_in_any_case() {
+    for nick in ["admin", "Server", "SYSTEM"] {
+        assert_eq!(validate_nick(nick), Err(NickError::Reserved), "{}", nick);
+    }
+}
+
+#[test]
+fn errors_read_as_user_facing_text() {
+    assert_eq!(NickError::Taken.to_string(), "Nama ini sedang dipakai");
+    assert!(NickError::TooShort.to_string().contains('2'));
+}
@@ -0,0 +1,20 @@
+use yewchat_protocol::{validate_room, RoomError, LOBBY, ROOM_MAX_LEN};
+
+#[test]
+fn ordinary_rooms_are_accepted() {
+    for room in [LOBBY, "rust", "kelas-b", "tim_2", &"x".repeat(ROOM_MAX_LEN)] {
+        assert_eq!(validate_room(room), Ok(()), "{}", room);
+    }
+}
+
+#[test]
+fn names_must_fit_in_a_url_as_they_are() {
+    assert_eq!(validate_room(""), Err(RoomError::Empty));
+    assert_eq!(
+        validate_room(&"x".repeat(ROOM_MAX_LEN + 1)),
+        Err(RoomError::TooLong)
+    );
+    assert_eq!(validate_room("Rust"), Err(RoomError::BadCharacter('R')));
+    assert_eq!(validate_room("a b"), Err(RoomError::BadCharacter(' ')));
+    assert_eq!(validate_room("a/b"), Err(RoomError::BadCharacter('/')));
+}
@@ -0,0 +1,328 @@
+//! Frames below are byte-for-byte what `SimpleWebsocketServer` and the
+//! original client put on the wire.
+
+use std::collections::BTreeMap;
+
+use yewchat_protocol::{
+    ClientMessage, DirectData, DirectRejectedData, EditData, MembersData, MessageData, PostData,
+    ReactionData, ReadData, ResumeData, ServerMessage, TypingData, LOBBY,
+};
+
+#[test]
+fn decodes_users_frame() {
+    let frame = r#"{"messageType":"users","dataArray":["alice","bob"]}"#;
+    let msg = ServerMessage::decode(frame).unwrap();
+    assert_eq!(
+        msg,
+        ServerMessage::Users(vec!["alice".into(), "bob".into()])
+    );
+    assert_eq!(msg.encode(), frame);
+}
+
+#[test]
+fn decodes_double_encoded_message_frame() {
+    let frame = r#"{"messageType":"message","data":"{\"from\":\"alice\",\"message\":\"halo\",\"time\":1700000000000}"}"#;
+    let msg = ServerMessage::decode(frame).unwrap();
+    assert_eq!(
+        msg,
+        ServerMessage::Message(MessageData {
+            id: None,
+            room: LOBBY.into(),
+            to: None,
+            parent_id: None,
+            from: "alice".into(),
+            message: "halo".into(),
+            time: 1_700_000_000_000,
+            edited: None,
+            edits: vec![],
+            deleted: false,
+            reactions: BTreeMap::new(),
+        })
+    );
+    assert_eq!(msg.encode(), frame);
+}
+
+#[test]
+fn decodes_legacy_client_frames() {
+    let register = r#"{"messageType":"register","dataArray":null,"data":"alice"}"#;
+    assert_eq!(
+        ClientMessage::decode(register).unwrap(),
+        ClientMessage::Register("alice".into())
+    );
+
+    let message = r#"{"messageType":"message","dataArray":null,"data":"halo semua"}"#;
+    assert_eq!(
+        ClientMessage::decode(message).unwrap(),
+        ClientMessage::Message("halo semua".into())
+    );
+}
+
+#[test]
+fn client_frames_round_trip() {
+    for msg in [
+        ClientMessage::Register("alice".into()),
+        ClientMessage::Message("pesan dengan \"kutip\"".into()),
+    ] {
+        assert_eq!(ClientMessage::decode(&msg.encode()).unwrap(), msg);
+    }
+    assert_eq!(
+        ClientMessage::Register("alice".into()).encode(),
+        r#"{"messageType":"register","data":"alice"}"#
+    );
+}
+
+#[test]
+fn serde_goes_through_the_envelope() {
+    let msg = ServerMessage::Users(vec!["alice".into()]);
+    let json = serde_json::to_string(&msg).unwrap();
+    assert_eq!(json, msg.encode());
+    assert_eq!(serde_json::from_str::<ServerMessage>(&json).unwrap(), msg);
+}
+
+#[test]
+fn direction_is_enforced() {
+    assert!(ServerMessage::decode(r#"{"messageType":"register","data":"alice"}"#).is_err());
+    assert!(ClientMessage::decode(r#"{"messageType":"users","dataArray":[]}"#).is_err());
+}
+
+#[test]
+fn register_replies_carry_their_text_in_data() {
+    let ack = r#"{"messageType":"registerAck","data":"alice"}"#;
+    assert_eq!(
+        ServerMessage::decode(ack).unwrap(),
+        ServerMessage::RegisterAck("alice".into())
+    );
+
+    let rejected = ServerMessage::RegisterRejected("Nama ini sedang dipakai".into());
+    assert_eq!(
+        rejected.encode(),
+        r#"{"messageType":"registerRejected","data":"Nama ini sedang dipakai"}"#
+    );
+    assert_eq!(ServerMessage::decode(&rejected.encode()).unwrap(), rejected);
+}
+
+#[test]
+fn resume_frames_round_trip() {
+    let resume = ClientMessage::Resume(ResumeData {
+        token: "abc".into(),
+        last_seen: Some(41),
+    });
+    assert_eq!(
+        resume.encode(),
+        r#"{"messageType":"resume","data":"{\"token\":\"abc\",\"lastSeen\":41}"}"#
+    );
+    assert_eq!(ClientMessage::decode(&resume.encode()).unwrap(), resume);
+
+    let token = ServerMessage::ResumeToken("abc".into());
+    assert_eq!(
+        token.encode(),
+        r#"{"messageType":"resumeToken","data":"abc"}"#
+    );
+    assert_eq!(ServerMessage::decode(&token.encode()).unwrap(), token);
+}
+
+#[test]
+fn numbered_messages_carry_their_id() {
+    let frame = r#"{"messageType":"message","data":"{\"id\":7,\"from\":\"alice\",\"message\":\"halo\",\"time\":1}"}"#;
+    let ServerMessage::Message(data) = ServerMessage::decode(frame).unwrap() else {
+        panic!("expected a message");
+    };
+    assert_eq!(data.id, Some(7));
+    assert_eq!(ServerMessage::Message(data).encode(), frame);
+}
+
+#[test]
+fn room_frames_round_trip() {
+    for msg in [
+        ClientMessage::Post(PostData {
+            room: "rust".into(),
+            message: "halo".into(),
+            parent_id: None,
+        }),
+        ClientMessage::Join("rust".into()),
+        ClientMessage::Leave("rust".into()),
+        ClientMessage::ListRooms,
+    ] {
+        assert_eq!(ClientMessage::decode(&msg.encode()).unwrap(), msg);
+    }
+    assert_eq!(
+        ClientMessage::ListRooms.encode(),
+        r#"{"messageType":"listRooms"}"#
+    );
+
+    for msg in [
+        ServerMessage::Rooms(vec![LOBBY.into(), "rust".into()]),
+        ServerMessage::Members(MembersData {
+            room: "rust".into(),
+            users: vec!["alice".into()],
+        }),
+    ] {
+        assert_eq!(ServerMessage::decode(&msg.encode()).unwrap(), msg);
+    }
+}
+
+#[test]
+fn messages_outside_the_lobby_name_their_room() {
+    let frame = r#"{"messageType":"message","data":"{\"id\":1,\"room\":\"rust\",\"from\":\"alice\",\"message\":\"halo\",\"time\":1}"}"#;
+    let ServerMessage::Message(data) = ServerMessage::decode(frame).unwrap() else {
+        panic!("expected a message");
+    };
+    assert_eq!(data.room, "rust");
+    assert_eq!(ServerMessage::Message(data).encode(), frame);
+}
+
+#[test]
+fn direct_frames_name_the_recipient() {
+    let direct = ClientMessage::Direct(DirectData {
+        to: "bob".into(),
+        message: "psst".into(),
+        parent_id: None,
+    });
+    assert_eq!(
+        direct.encode(),
+        r#"{"messageType":"direct","data":"{\"to\":\"bob\",\"message\":\"psst\"}"}"#
+    );
+    assert_eq!(ClientMessage::decode(&direct.encode()).unwrap(), direct);
+
+    let frame = r#"{"messageType":"direct","data":"{\"id\":3,\"to\":\"bob\",\"from\":\"alice\",\"message\":\"psst\",\"time\":1}"}"#;
+    let ServerMessage::Direct(data) = ServerMessage::decode(frame).unwrap() else {
+        panic!("expected a direct message");
+    };
+    assert_eq!(data.to.as_deref(), Some("bob"));
+    assert_eq!(data.room, LOBBY);
+    assert_eq!(ServerMessage::Direct(data).encode(), frame);
+}
+
+#[test]
+fn rejected_directs_carry_the_message_back() {
+    let rejected = ServerMessage::DirectRejected(DirectRejectedData {
+        direct: DirectData {
+            to: "bob".into(),
+            message: "psst".into(),
+            parent_id: None,
+        },
+        reason: "bob tidak sedang online".into(),
+    });
+    assert_eq!(
+        rejected.encode(),
+        r#"{"messageType":"directRejected","data":"{\"to\":\"bob\",\"message\":\"psst\",\"reason\":\"bob tidak sedang online\"}"}"#
+    );
+    assert_eq!(ServerMessage::decode(&rejected.encode()).unwrap(), rejected);
+}
+
+#[test]
+fn replies_name_their_parent() {
+    let reply = ClientMessage::Post(PostData {
+        room: "rust".into(),
+        message: "setuju".into(),
+        parent_id: Some(7),
+    });
+    assert_eq!(
+        reply.encode(),
+        r#"{"messageType":"post","data":"{\"room\":\"rust\",\"message\":\"setuju\",\"parentId\":7}"}"#
+    );
+    assert_eq!(ClientMessage::decode(&reply.encode()).unwrap(), reply);
+
+    let frame = r#"{"messageType":"message","data":"{\"id\":8,\"parentId\":7,\"from\":\"bob\",\"message\":\"setuju\",\"time\":1}"}"#;
+    let ServerMessage::Message(data) = ServerMessage::decode(frame).unwrap() else {
+        panic!("expected a message");
+    };
+    assert_eq!(data.parent_id, Some(7));
+    assert_eq!(ServerMessage::Message(data).encode(), frame);
+}
+
+#[test]
+fn edit_frames_round_trip() {
+    for msg in [
+        ClientMessage::Edit(EditData {
+            id: 7,
+            message: "halo semua".into(),
+        }),
+        ClientMessage::Delete(7),
+    ] {
+        assert_eq!(ClientMessage::decode(&msg.encode()).unwrap(), msg);
+    }
+    assert_eq!(
+        ServerMessage::Delete(7).encode(),
+        r#"{"messageType":"delete","data":"7"}"#
+    );
+
+    let frame = r#"{"messageType":"edit","data":"{\"id\":7,\"from\":\"alice\",\"message\":\"halo semua\",\"time\":1,\"edited\":5,\"edits\":[{\"message\":\"halo smua\",\"time\":1}]}"}"#;
+    let ServerMessage::Edit(data) = ServerMessage::decode(frame).unwrap() else {
+        panic!("expected an edit");
+    };
+    assert_eq!(data.edits[0].message, "halo smua");
+    assert!(!data.deleted);
+    assert_eq!(ServerMessage::Edit(data).encode(), frame);
+}
+
+#[test]
+fn reaction_frames_round_trip() {
+    let react = ClientMessage::React(ReactionData {
+        id: 7,
+        emoji: "👍".into(),
+        from: None,
+    });
+    assert_eq!(
+        react.encode(),
+        r#"{"messageType":"react","data":"{\"id\":7,\"emoji\":\"👍\"}"}"#
+    );
+    assert_eq!(ClientMessage::decode(&react.encode()).unwrap(), react);
+
+    let unreact = ServerMessage::Unreact(ReactionData {
+        id: 7,
+        emoji: "👍".into(),
+        from: Some("bob".into()),
+    });
+    assert_eq!(ServerMessage::decode(&unreact.encode()).unwrap(), unreact);
+
+    let frame = r#"{"messageType":"message","data":"{\"id\":7,\"from\":\"alice\",\"message\":\"halo\",\"time\":1,\"reactions\":{\"👍\":[\"bob\"]}}"}"#;
+    let ServerMessage::Message(data) = ServerMessage::decode(frame).unwrap() else {
+        panic!("expected a message");
+    };
+    assert_eq!(data.reactions["👍"], ["bob"]);
+    assert_eq!(ServerMessage::Message(data).encode(), frame);
+}
+
+#[test]
+fn typing_frames_round_trip() {
+    let typing = ClientMessage::Typing(TypingData {
+        room: LOBBY.into(),
+        to: None,
+        from: None,
+        typing: true,
+    });
+    assert_eq!(
+        typing.encode(),
+        r#"{"messageType":"typing","data":"{\"typing\":true}"}"#
+    );
+    assert_eq!(ClientMessage::decode(&typing.encode()).unwrap(), typing);
+
+    let stopped = ServerMessage::Typing(TypingData {
+        room: LOBBY.into(),
+        to: Some("bob".into()),
+        from: Some("alice".into()),
+        typing: false,
+    });
+    assert_eq!(
+        stopped.encode(),
+        r#"{"messageType":"typing","data":"{\"to\":\"bob\",\"from\":\"alice\",\"typing\":false}"}"#
+    );
+    assert_eq!(ServerMessage::decode(&stopped.encode()).unwrap(), stopped);
+}
+
+#[test]
+fn read_frames_round_trip() {
+    let read = ClientMessage::Read(ReadData { id: 7, from: None });
+    assert_eq!(
+        read.encode(),
+        r#"{"messageType":"read","data":"{\"id\":7}"}"#
+    );
+    assert_eq!(ClientMessage::decode(&read.encode()).unwrap(), read);
+
+    let read = ServerMessage::Read(ReadData {
+        id: 7,
+        from: Some("bob".into()),
+    });
+    assert_eq!(ServerMessage::decode(&read.encode()).unwrap(), read);
+}
@@ -0,0 +1,209 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::Duration;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use yewchat_protocol::{validate_nick, AuthRequest, AuthToken};
+
+use crate::hub::now;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How long tokens stay valid unless configured otherwise.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+/// Invite code accepted when none are configured, so a fresh checkout works.
+pub const DEV_INVITE: &str = "yewchat";
+
+/// Checks credentials and issues and verifies signed session tokens.
+///
+/// A token is `base64(nick:expires_at).base64(hmac)`, signed with a key only
+/// this process knows, so it proves the server vouched for `nick` until
+/// `expires_at`.
+pub struct Auth {
+    key: Vec<u8>,
+    ttl: Duration,
+    /// Nicknames with an account, and their passwords.
+    passwords: HashMap<String, String>,
+    /// Codes that let anyone claim a nickname without an account.
+    invites: HashSet<String>,
+}
+
+/// Why credentials or a token were refused. The `Display` text is sent to
+/// users.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthFailure {
+    /// The nickname itself is not allowed; carries the rule it breaks.
+    Nick(String),
+    WrongSecret,
+    BadToken,
+    Expired,
+}
+
+impl fmt::Display for AuthFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthFailure::Nick(reason) => f.write_str(reason),
+            AuthFailure::WrongSecret => f.write_str("Kata sandi atau kode undangan salah"),
+            AuthFailure::BadToken => f.write_str("Token tidak valid"),
+            AuthFailure::Expired => f.write_str("Sesi telah berakhir"),
+        }
+    }
+}
+
+impl std::error::Error for AuthFailure {}
+
+/// What a valid token says.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Claims {
+    pub nick: String,
+    /// Milliseconds since the Unix epoch.
+    pub expires_at: u64,
+}
+
+impl Auth {
+    /// An authority signing with `key` and handing out tokens valid for `ttl`,
+    /// with no accounts or invites yet.
+    pub fn new(key: &[u8], ttl: Duration) -> Self {
+        Self {
+            key: key.to_vec(),
+            ttl,
+            passwords: HashMap::new(),
+            invites: HashSet::new(),
+        }
+    }
+
+    /// Lets `nick` in with `password`, and nobody else under that name.
+    pub fn with_password(mut self, nick: &str, password: &str) -> Self {
+        self.passwords
+            .insert(nick.to_ascii_lowercase(), password.to_owned());
+        self
+    }
+
+    pub fn with_invite(mut self, code: &str) -> Self {
+        self.invites.insert(code.to_owned());
+        self
+    }
+
+    /// Reads the configuration from the environment:
+    ///
+    /// - `YEWCHAT_SECRET`: signing key; random per process when unset, which
+    ///   invalidates every token on restart.
+    /// - `YEWCHAT_TOKEN_TTL`: token lifetime in seconds.
+    /// - `YEWCHAT_USERS`: accounts as `nick:password,nick:password`.
+    /// - `YEWCHAT_INVITES`: invite codes as `code,code`; [`DEV_INVITE`] when
+    ///   unset.
+    pub fn from_env() -> Self {
+        let key = match std::env::var("YEWCHAT_SECRET") {
+            Ok(secret) => secret.into_bytes(),
+            Err(_) => {
+                let mut key = vec![0; 32];
+                getrandom::fill(&mut key).expect("no source of randomness");
+                key
+            }
+        };
+        let ttl = std::env::var("YEWCHAT_TOKEN_TTL")
+            .ok()
+            .map(|s| {
+                s.parse()
+                    .expect("YEWCHAT_TOKEN_TTL must be a number of seconds")
+            })
+            .map_or(DEFAULT_TTL, Duration::from_secs);
+        let mut auth = Self::new(&key, ttl);
+
+        for account in list("YEWCHAT_USERS") {
+            match account.split_once(':') {
+                Some((nick, password)) => auth = auth.with_password(nick, password),
+                None => log::warn!("ignoring account without a password: {}", account),
+            }
+        }
+        let invites = list("YEWCHAT_INVITES");
+        if invites.is_empty() {
+            log::warn!(
+                "no YEWCHAT_INVITES set, accepting invite code {:?}",
+                DEV_INVITE
+            );
+            auth = auth.with_invite(DEV_INVITE);
+        }
+        for code in invites {
+            auth = auth.with_invite(&code);
+        }
+        auth
+    }
+
+    /// Checks `request` and issues a token for its nickname.
+    ///
+    /// Nicknames with an account need their password; any other valid
+    /// nickname gets in with an invite code.
+    pub fn login(&self, request: &AuthRequest) -> Result<AuthToken, AuthFailure> {
+        validate_nick(&request.nick).map_err(|e| AuthFailure::Nick(e.to_string()))?;
+        let allowed = match self.passwords.get(&request.nick.to_ascii_lowercase()) {
+            Some(password) => *password == request.secret,
+            None => self.invites.contains(&request.secret),
+        };
+        if !allowed {
+            return Err(AuthFailure::WrongSecret);
+        }
+        Ok(self.issue(&request.nick))
+    }
+
+    /// A token for `nick`, valid from now for the configured lifetime.
+    pub fn issue(&self, nick: &str) -> AuthToken {
+        let expires_at = now() + self.ttl.as_millis() as u64;
+        let payload = format!("{}:{}", nick, expires_at);
+        let signature = self.mac(payload.as_bytes()).finalize().into_bytes();
+        AuthToken {
+            token: format!(
+                "{}.{}",
+                URL_SAFE_NO_PAD.encode(payload),
+                URL_SAFE_NO_PAD.encode(signature)
+            ),
+            expires_at,
+        }
+    }
+
+    /// What `token` vouches for, if this server signed it and it has not
+    /// expired.
+    pub fn verify(&self, token: &str) -> Result<Claims, AuthFailure> {
+        let (payload, signature) = token.split_once('.').ok_or(AuthFailure::BadToken)?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| AuthFailure::BadToken)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| AuthFailure::BadToken)?;
+        self.mac(&payload)
+            .verify_slice(&signature)
+            .map_err(|_| AuthFailure::BadToken)?;
+
+        let payload = String::from_utf8(payload).map_err(|_| AuthFailure::BadToken)?;
+        let (nick, expires_at) = payload.rsplit_once(':').ok_or(AuthFailure::BadToken)?;
+        let expires_at: u64 = expires_at.parse().map_err(|_| AuthFailure::BadToken)?;
+        if expires_at <= now() {
+            return Err(AuthFailure::Expired);
+        }
+        Ok(Claims {
+            nick: nick.to_owned(),
+            expires_at,
+        })
+    }
+
+    fn mac(&self, payload: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC takes keys of any size");
+        mac.update(payload);
+        mac
+    }
+}
+
+/// A comma-separated environment variable, with blanks dropped.
+fn list(var: &str) -> Vec<String> {
+    std::env::var(var)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
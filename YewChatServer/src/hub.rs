@@ -0,0 +1,798 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use yewchat_protocol::{
+    validate_nick, validate_room, ClientMessage, DirectData, DirectRejectedData, EditData,
+    MembersData, MessageData, NickError, PostData, ReactionData, ReadData, ResumeData,
+    ServerMessage, TypingData, LOBBY, REACTIONS,
+};
+
+/// Identifies one socket for as long as it stays connected.
+pub type ClientId = u64;
+
+/// How long a nickname whose socket dropped stays reserved for a resume,
+/// unless configured otherwise.
+pub const DEFAULT_GRACE: Duration = Duration::from_secs(30);
+/// How many recent messages are kept for replay on resume, and so can still
+/// be edited, deleted or reacted to.
+pub const HISTORY_LEN: usize = 500;
+
+/// Everyone connected to the server and the nicknames they registered.
+///
+/// Sessions hand every inbound frame to [`Hub::receive`] and forward whatever
+/// arrives on the receiver from [`Hub::connect`] to their socket. Each socket
+/// may only register the nickname its token was issued for.
+///
+/// Registered users start out in the [`LOBBY`] and may join other rooms,
+/// which exist for as long as someone is in them. Messages only go to the
+/// members of their room, direct messages only to their two ends.
+///
+/// A nickname whose socket drops without a close frame stays online for a
+/// grace period, so the client can `resume` it and get the messages it
+/// missed replayed.
+#[derive(Clone)]
+pub struct Hub {
+    inner: Arc<Mutex<Inner>>,
+    grace: Duration,
+}
+
+impl Default for Hub {
+    fn default() -> Self {
+        Self::new(DEFAULT_GRACE)
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: ClientId,
+    clients: BTreeMap<ClientId, Client>,
+    /// Registered nicknames, in the order they joined.
+    users: Vec<User>,
+    /// Members of every room but the lobby by nickname, in the order they
+    /// joined. Everyone registered is in the lobby.
+    rooms: BTreeMap<String, Vec<String>>,
+    /// Id of the newest message, 0 before the first.
+    last_message: u64,
+    /// The last [`HISTORY_LEN`] messages, oldest first.
+    history: VecDeque<MessageData>,
+    /// For messages in `history` that were edited, deleted or reacted to,
+    /// the newest message id at the time of the last change.
+    revised: BTreeMap<u64, u64>,
+    /// Id of the newest message each user has read, per conversation.
+    reads: BTreeMap<Conversation, BTreeMap<String, u64>>,
+}
+
+/// Where a message was said: a room, or between two users, in order.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Conversation {
+    Room(String),
+    Direct(String, String),
+}
+
+impl Conversation {
+    fn of(data: &MessageData) -> Self {
+        match &data.to {
+            None => Self::Room(data.room.clone()),
+            Some(to) => {
+                let mut ends = [data.from.clone(), to.clone()];
+                ends.sort();
+                let [a, b] = ends;
+                Self::Direct(a, b)
+            }
+        }
+    }
+}
+
+struct User {
+    nick: String,
+    /// `None` while the socket is gone and the nickname waits for a resume.
+    client: Option<ClientId>,
+    /// Proves a later socket is the same user.
+    resume: String,
+    /// The newest message when the user registered, which bounds a replay
+    /// for a client that has not seen any message yet.
+    joined_after: u64,
+}
+
+struct Client {
+    tx: UnboundedSender<String>,
+    /// The nickname this socket's token vouches for.
+    authorized: String,
+}
+
+impl Hub {
+    /// A hub that holds dropped nicknames for `grace`.
+    pub fn new(grace: Duration) -> Self {
+        Self {
+            inner: Arc::default(),
+            grace,
+        }
+    }
+
+    /// Adds a socket, authorized to register as `authorized`, that will
+    /// receive every broadcast from now on.
+    pub fn connect(&self, authorized: String) -> (ClientId, UnboundedReceiver<String>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.clients.insert(id, Client { tx, authorized });
+        (id, rx)
+    }
+
+    /// Removes a socket that said goodbye, telling everyone else if it had a
+    /// nickname.
+    pub fn leave(&self, id: ClientId) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.clients.remove(&id);
+        inner.remove_users(|user| user.client == Some(id));
+    }
+
+    /// Removes a socket that dropped, keeping its nickname for a resume until
+    /// the grace period is over.
+    pub fn disconnect(&self, id: ClientId) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.clients.remove(&id);
+        let Some(user) = inner.users.iter_mut().find(|u| u.client == Some(id)) else {
+            return;
+        };
+        log::info!("client {}: holding {:?} for a resume", id, user.nick);
+        user.client = None;
+        let resume = user.resume.clone();
+        drop(inner);
+
+        let hub = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(hub.grace).await;
+            hub.expire(&resume);
+        });
+    }
+
+    /// Lets go of a held nickname that was not resumed in time.
+    fn expire(&self, resume: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.remove_users(|user| user.client.is_none() && user.resume == resume);
+    }
+
+    /// Handles one text frame from `id`.
+    pub fn receive(&self, id: ClientId, frame: &str) {
+        let msg = match ClientMessage::decode(frame) {
+            Ok(msg) => msg,
+            Err(e) => {
+                log::warn!("client {}: dropping frame: {}: {}", id, e, frame);
+                return;
+            }
+        };
+
+        let mut inner = self.inner.lock().unwrap();
+        match msg {
+            ClientMessage::Register(nick) => inner.register(id, nick),
+            ClientMessage::Resume(resume) => inner.resume(id, resume),
+            ClientMessage::Message(message) => inner.post(
+                id,
+                PostData {
+                    room: LOBBY.into(),
+                    message,
+                    parent_id: None,
+                },
+            ),
+            ClientMessage::Post(post) => inner.post(id, post),
+            ClientMessage::Join(room) => inner.join_room(id, room),
+            ClientMessage::Leave(room) => inner.leave_room(id, &room),
+            ClientMessage::ListRooms => inner.send_to(id, &inner.rooms_frame()),
+            ClientMessage::Direct(direct) => inner.direct(id, direct),
+            ClientMessage::Edit(edit) => inner.edit(id, edit),
+            ClientMessage::Delete(message) => inner.delete(id, message),
+            ClientMessage::React(reaction) => inner.react(id, reaction, true),
+            ClientMessage::Unreact(reaction) => inner.react(id, reaction, false),
+            ClientMessage::Typing(typing) => inner.typing(id, typing),
+            ClientMessage::Read(read) => inner.read(id, read),
+        }
+    }
+}
+
+impl Inner {
+    fn register(&mut self, id: ClientId, nick: String) {
+        if let Err(reason) = self.check(id, &nick) {
+            log::info!("client {}: refusing {:?}: {}", id, nick, reason);
+            self.send_to(id, &ServerMessage::RegisterRejected(reason));
+            return;
+        }
+        log::info!("client {} registered as {:?}", id, nick);
+        let joined = if let Some(user) = self.users.iter_mut().find(|u| u.client == Some(id)) {
+            user.nick = nick;
+            true
+        } else if let Some(user) = self
+            .users
+            .iter_mut()
+            .find(|u| u.client.is_none() && u.nick == nick)
+        {
+            // The same person is back before their hold ran out, so the
+            // roster does not change.
+            user.client = Some(id);
+            user.resume = resume_token();
+            false
+        } else {
+            self.users.push(User {
+                nick,
+                client: Some(id),
+                resume: resume_token(),
+                joined_after: self.last_message,
+            });
+            true
+        };
+        self.welcome(id, None, joined);
+    }
+
+    /// Hands the nickname held by `resume.token` to `id`, replacing a socket
+    /// that may not have noticed it is dead yet, under a new token so the
+    /// hold from an earlier drop cannot expire it. Unknown or expired tokens
+    /// fall back to registering the nickname on the socket's token.
+    fn resume(&mut self, id: ClientId, resume: ResumeData) {
+        let Some(authorized) = self.clients.get(&id).map(|c| c.authorized.clone()) else {
+            return;
+        };
+        if self.users.iter().any(|u| u.client == Some(id)) {
+            log::warn!("client {}: resume after register", id);
+            return;
+        }
+        let held = self
+            .users
+            .iter()
+            .position(|u| u.resume == resume.token && u.nick == authorized);
+        let Some(i) = held else {
+            log::info!("client {}: cannot resume, registering instead", id);
+            self.register(id, authorized);
+            return;
+        };
+        log::info!("client {} resumed {:?}", id, authorized);
+        let user = &mut self.users[i];
+        user.resume = resume_token();
+        if let Some(stale) = user.client.replace(id).filter(|stale| *stale != id) {
+            // Dropping its sender ends the stale session.
+            self.clients.remove(&stale);
+        }
+        let after = resume.last_seen.unwrap_or(self.users[i].joined_after);
+        self.welcome(id, Some(after), false);
+    }
+
+    /// Acknowledges the user on `id`, replaying the messages after
+    /// `replay_after` if given, and the new version of those up to it that
+    /// changed since. `joined` is whether the roster changed.
+    fn welcome(&self, id: ClientId, replay_after: Option<u64>, joined: bool) {
+        let Some(user) = self.users.iter().find(|u| u.client == Some(id)) else {
+            return;
+        };
+        self.send_to(id, &ServerMessage::RegisterAck(user.nick.clone()));
+        self.send_to(id, &ServerMessage::ResumeToken(user.resume.clone()));
+        if let Some(after) = replay_after {
+            // A change made once `after` was the newest message may have
+            // been missed, so it counts.
+            let changed = self.history.iter().filter(|m| {
+                m.id <= Some(after)
+                    && m.id.and_then(|id| self.revised.get(&id)) >= Some(&after)
+                    && self.can_see(m, &user.nick)
+            });
+            for data in changed {
+                self.send_to(id, &ServerMessage::Edit(data.clone()));
+            }
+            let missed = self
+                .history
+                .iter()
+                .filter(|m| m.id > Some(after) && self.can_see(m, &user.nick));
+            for data in missed {
+                self.send_to(id, &message_frame(data.clone()));
+            }
+        }
+        let reads = self
+            .reads
+            .iter()
+            .filter(|(conversation, _)| self.takes_part(conversation, &user.nick));
+        for (from, &read) in reads.flat_map(|(_, markers)| markers) {
+            let read = ReadData {
+                id: read,
+                from: Some(from.clone()),
+            };
+            self.send_to(id, &ServerMessage::Read(read));
+        }
+        if joined {
+            self.broadcast_users();
+        } else {
+            self.send_to(id, &self.users_frame());
+        }
+    }
+
+    /// Whether `id` may go by `nick`, or why not. It must be the nickname
+    /// the socket's token was issued for, and nobody else may be online
+    /// under it in any case.
+    fn check(&self, id: ClientId, nick: &str) -> Result<(), String> {
+        validate_nick(nick).map_err(|e| e.to_string())?;
+        if self.clients.get(&id).is_none_or(|c| c.authorized != nick) {
+            return Err("Nama tidak sesuai dengan token".into());
+        }
+        // A held nickname is only free for the exact name it was held for,
+        // which the token check above has vouched for.
+        let taken = self.users.iter().any(|user| {
+            user.client != Some(id)
+                && user.nick.eq_ignore_ascii_case(nick)
+                && (user.client.is_some() || user.nick != nick)
+        });
+        if taken {
+            return Err(NickError::Taken.to_string());
+        }
+        Ok(())
+    }
+
+    /// The nickname registered on `id`. Like the TypeScript server, anything
+    /// but `register` from sockets that never did is ignored.
+    fn registered(&self, id: ClientId) -> Option<String> {
+        let nick = self
+            .users
+            .iter()
+            .find(|u| u.client == Some(id))
+            .map(|u| u.nick.clone());
+        if nick.is_none() {
+            log::warn!("client {}: ignoring frame before register", id);
+        }
+        nick
+    }
+
+    fn post(&mut self, id: ClientId, post: PostData) {
+        let Some(from) = self.registered(id) else {
+            return;
+        };
+        if !self.is_member(&post.room, &from) {
+            log::warn!("{:?} posting to {:?} without joining", from, post.room);
+            return;
+        }
+        let Some(data) = self.record(MessageData {
+            id: None,
+            room: post.room,
+            to: None,
+            parent_id: post.parent_id,
+            from,
+            message: post.message,
+            time: now(),
+            edited: None,
+            edits: vec![],
+            deleted: false,
+            reactions: BTreeMap::new(),
+        }) else {
+            return;
+        };
+        self.send_to_room(&data.room, &ServerMessage::Message(data.clone()));
+    }
+
+    /// Relays a direct message to its recipient, who may be waiting for a
+    /// resume, and back to the sender. Only the sender hears of one sent to
+    /// nobody.
+    fn direct(&mut self, id: ClientId, direct: DirectData) {
+        let Some(from) = self.registered(id) else {
+            return;
+        };
+        let refusal = if direct.to == from {
+            Some("Tidak bisa mengirim pesan pribadi ke diri sendiri".to_owned())
+        } else if !self.users.iter().any(|u| u.nick == direct.to) {
+            Some(format!("{} tidak sedang online", direct.to))
+        } else {
+            None
+        };
+        if let Some(reason) = refusal {
+            log::info!("{:?} cannot message {:?}: {}", from, direct.to, reason);
+            self.send_to(
+                id,
+                &ServerMessage::DirectRejected(DirectRejectedData { direct, reason }),
+            );
+            return;
+        }
+        let Some(data) = self.record(MessageData {
+            id: None,
+            room: LOBBY.into(),
+            to: Some(direct.to),
+            parent_id: direct.parent_id,
+            from,
+            message: direct.message,
+            time: now(),
+            edited: None,
+            edits: vec![],
+            deleted: false,
+            reactions: BTreeMap::new(),
+        }) else {
+            return;
+        };
+        self.send_to_readers(&data, &ServerMessage::Direct(data.clone()));
+    }
+
+    /// Changes the text of a message at its author's request, keeping the
+    /// old text in its history.
+    fn edit(&mut self, id: ClientId, edit: EditData) {
+        let Some(data) = self.authored(id, edit.id) else {
+            return;
+        };
+        if edit.message.trim().is_empty() || edit.message == data.message {
+            return;
+        }
+        data.edit(edit.message, now());
+        let data = data.clone();
+        self.revise(edit.id);
+        self.send_to_readers(&data, &ServerMessage::Edit(data.clone()));
+    }
+
+    /// Leaves only a tombstone of a message at its author's request, history
+    /// and all.
+    fn delete(&mut self, id: ClientId, message: u64) {
+        let Some(data) = self.authored(id, message) else {
+            return;
+        };
+        data.delete();
+        let data = data.clone();
+        self.revise(message);
+        self.send_to_readers(&data, &ServerMessage::Delete(message));
+    }
+
+    /// Adds the reaction of the user on `id`, or takes it back, telling
+    /// everyone who can see the message if that changed anything.
+    fn react(&mut self, id: ClientId, reaction: ReactionData, add: bool) {
+        let Some(nick) = self.registered(id) else {
+            return;
+        };
+        if !REACTIONS.contains(&reaction.emoji.as_str()) {
+            log::warn!("{:?} reacting with {:?}", nick, reaction.emoji);
+            return;
+        }
+        let Some(i) = self
+            .history
+            .iter()
+            .position(|m| m.id == Some(reaction.id) && !m.deleted)
+        else {
+            log::warn!(
+                "{:?} reacting to unknown or old message {}",
+                nick,
+                reaction.id
+            );
+            return;
+        };
+        if !self.can_see(&self.history[i], &nick) {
+            log::warn!("{:?} reacting to {} from elsewhere", nick, reaction.id);
+            return;
+        }
+        let data = &mut self.history[i];
+        let changed = if add {
+            data.react(&reaction.emoji, &nick)
+        } else {
+            data.unreact(&reaction.emoji, &nick)
+        };
+        if !changed {
+            return;
+        }
+        let data = data.clone();
+        self.revise(reaction.id);
+        let reaction = ReactionData {
+            from: Some(nick),
+            ..reaction
+        };
+        let msg = if add {
+            ServerMessage::React(reaction)
+        } else {
+            ServerMessage::Unreact(reaction)
+        };
+        self.send_to_readers(&data, &msg);
+    }
+
+    /// Passes on that the user on `id` started or stopped typing to the
+    /// others in the conversation.
+    fn typing(&self, id: ClientId, typing: TypingData) {
+        let Some(from) = self.registered(id) else {
+            return;
+        };
+        let allowed = match &typing.to {
+            Some(to) => *to != from && self.users.iter().any(|u| u.nick == *to),
+            None => self.is_member(&typing.room, &from),
+        };
+        if !allowed {
+            log::warn!("{:?} typing where they cannot: {:?}", from, typing);
+            return;
+        }
+        let readers: Vec<ClientId> = self
+            .users
+            .iter()
+            .filter(|u| u.nick != from)
+            .filter(|u| match &typing.to {
+                Some(to) => u.nick == *to,
+                None => self.is_member(&typing.room, &u.nick),
+            })
+            .filter_map(|u| u.client)
+            .collect();
+        let msg = ServerMessage::Typing(TypingData {
+            from: Some(from),
+            ..typing
+        });
+        for id in readers {
+            self.send_to(id, &msg);
+        }
+    }
+
+    /// Moves the read marker of the user on `id` up to a message, telling
+    /// everyone who can see it. Markers never move back.
+    fn read(&mut self, id: ClientId, read: ReadData) {
+        let Some(nick) = self.registered(id) else {
+            return;
+        };
+        let Some(data) = self.history.iter().find(|m| m.id == Some(read.id)) else {
+            log::warn!("{:?} reading unknown or old message {}", nick, read.id);
+            return;
+        };
+        if !self.can_see(data, &nick) {
+            log::warn!("{:?} reading {} from elsewhere", nick, read.id);
+            return;
+        }
+        let data = data.clone();
+        let marker = self
+            .reads
+            .entry(Conversation::of(&data))
+            .or_default()
+            .entry(nick.clone())
+            .or_default();
+        if *marker >= read.id {
+            return;
+        }
+        *marker = read.id;
+        let read = ReadData {
+            id: read.id,
+            from: Some(nick),
+        };
+        self.send_to_readers(&data, &ServerMessage::Read(read));
+    }
+
+    /// Message `message`, if the user on `id` wrote it and it can still be
+    /// changed.
+    fn authored(&mut self, id: ClientId, message: u64) -> Option<&mut MessageData> {
+        let nick = self.registered(id)?;
+        let Some(data) = self.history.iter_mut().find(|m| m.id == Some(message)) else {
+            log::warn!("{:?} changing unknown or old message {}", nick, message);
+            return None;
+        };
+        if data.from != nick || data.deleted {
+            log::warn!("{:?} cannot change message {}", nick, message);
+            return None;
+        }
+        Some(data)
+    }
+
+    /// Notes that message `id` changed, for replays.
+    fn revise(&mut self, id: u64) {
+        self.revised.insert(id, self.last_message);
+    }
+
+    /// Numbers `data` and keeps it for replay. A reply is filed under the
+    /// root of its parent's thread, or refused if it cannot be.
+    fn record(&mut self, mut data: MessageData) -> Option<MessageData> {
+        if let Some(parent_id) = data.parent_id {
+            data.parent_id = Some(self.thread_root(parent_id, &data)?);
+        }
+        self.last_message += 1;
+        data.id = Some(self.last_message);
+        if self.history.len() == HISTORY_LEN {
+            if let Some(id) = self.history.pop_front().and_then(|m| m.id) {
+                self.revised.remove(&id);
+            }
+        }
+        self.history.push_back(data.clone());
+        Some(data)
+    }
+
+    /// The thread `reply` to `parent_id` belongs in, if the parent exists and
+    /// was said in the same conversation.
+    fn thread_root(&self, parent_id: u64, reply: &MessageData) -> Option<u64> {
+        if parent_id == 0 || parent_id > self.last_message {
+            log::warn!("{:?} replying to unknown message {}", reply.from, parent_id);
+            return None;
+        }
+        // Parents that fell out of the history are taken on trust.
+        let Some(parent) = self.history.iter().find(|m| m.id == Some(parent_id)) else {
+            return Some(parent_id);
+        };
+        if !same_conversation(parent, reply) {
+            log::warn!("{:?} replying to {} from elsewhere", reply.from, parent_id);
+            return None;
+        }
+        Some(parent.parent_id.unwrap_or(parent_id))
+    }
+
+    /// Puts the user on `id` in `room`, creating it if needed. The joiner
+    /// always gets the member list, even if it was already in.
+    fn join_room(&mut self, id: ClientId, room: String) {
+        let Some(nick) = self.registered(id) else {
+            return;
+        };
+        if let Err(e) = validate_room(&room) {
+            log::warn!("client {}: cannot join {:?}: {}", id, room, e);
+            return;
+        }
+        if self.is_member(&room, &nick) {
+            self.send_to(id, &self.members_frame(&room));
+            return;
+        }
+        let created = !self.rooms.contains_key(&room);
+        self.rooms.entry(room.clone()).or_default().push(nick);
+        if created {
+            self.broadcast(&self.rooms_frame());
+        }
+        self.send_to_room(&room, &self.members_frame(&room));
+    }
+
+    fn leave_room(&mut self, id: ClientId, room: &str) {
+        let Some(nick) = self.registered(id) else {
+            return;
+        };
+        if room == LOBBY {
+            log::warn!("{:?} cannot leave the lobby", nick);
+            return;
+        }
+        self.exit(room, &nick);
+    }
+
+    /// Takes `nick` out of `room`, telling whoever is left, and removes the
+    /// room once it is empty.
+    fn exit(&mut self, room: &str, nick: &str) {
+        let Some(members) = self.rooms.get_mut(room) else {
+            return;
+        };
+        let before = members.len();
+        members.retain(|member| member != nick);
+        if members.len() == before {
+            return;
+        }
+        let conversation = Conversation::Room(room.to_owned());
+        if let Some(markers) = self.reads.get_mut(&conversation) {
+            markers.remove(nick);
+        }
+        if members.is_empty() {
+            self.reads.remove(&conversation);
+            self.rooms.remove(room);
+            self.broadcast(&self.rooms_frame());
+        } else {
+            self.send_to_room(room, &self.members_frame(room));
+        }
+    }
+
+    /// Forgets the users matching `gone`, taking them out of their rooms and
+    /// telling everyone else.
+    fn remove_users(&mut self, gone: impl Fn(&User) -> bool) {
+        let (removed, kept): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut self.users).into_iter().partition(gone);
+        self.users = kept;
+        if removed.is_empty() {
+            return;
+        }
+        let rooms: Vec<String> = self.rooms.keys().cloned().collect();
+        for user in &removed {
+            for room in &rooms {
+                self.exit(room, &user.nick);
+            }
+        }
+        for markers in self.reads.values_mut() {
+            markers.retain(|nick, _| removed.iter().all(|user| user.nick != *nick));
+        }
+        self.reads.retain(|_, markers| !markers.is_empty());
+        self.broadcast_users();
+    }
+
+    fn is_member(&self, room: &str, nick: &str) -> bool {
+        if room == LOBBY {
+            return self.users.iter().any(|u| u.nick == nick);
+        }
+        self.rooms
+            .get(room)
+            .is_some_and(|members| members.iter().any(|member| member == nick))
+    }
+
+    /// Whether `data` is meant for `nick`.
+    fn can_see(&self, data: &MessageData, nick: &str) -> bool {
+        self.takes_part(&Conversation::of(data), nick)
+    }
+
+    fn takes_part(&self, conversation: &Conversation, nick: &str) -> bool {
+        match conversation {
+            Conversation::Room(room) => self.is_member(room, nick),
+            Conversation::Direct(a, b) => a == nick || b == nick,
+        }
+    }
+
+    fn members(&self, room: &str) -> Vec<String> {
+        if room == LOBBY {
+            return self.users.iter().map(|u| u.nick.clone()).collect();
+        }
+        self.rooms.get(room).cloned().unwrap_or_default()
+    }
+
+    /// The lobby first, then the others by name.
+    fn rooms_frame(&self) -> ServerMessage {
+        let rooms = std::iter::once(LOBBY.to_owned())
+            .chain(self.rooms.keys().cloned())
+            .collect();
+        ServerMessage::Rooms(rooms)
+    }
+
+    fn members_frame(&self, room: &str) -> ServerMessage {
+        ServerMessage::Members(MembersData {
+            room: room.to_owned(),
+            users: self.members(room),
+        })
+    }
+
+    /// Sends `msg` to everyone connected who can see `data`.
+    fn send_to_readers(&self, data: &MessageData, msg: &ServerMessage) {
+        for user in self.users.iter().filter(|u| self.can_see(data, &u.nick)) {
+            if let Some(id) = user.client {
+                self.send_to(id, msg);
+            }
+        }
+    }
+
+    /// Sends `msg` to every connected member of `room`.
+    fn send_to_room(&self, room: &str, msg: &ServerMessage) {
+        let frame = msg.encode();
+        for user in self.users.iter().filter(|u| self.is_member(room, &u.nick)) {
+            if let Some(client) = user.client.and_then(|id| self.clients.get(&id)) {
+                let _ = client.tx.send(frame.clone());
+            }
+        }
+    }
+
+    fn send_to(&self, id: ClientId, msg: &ServerMessage) {
+        if let Some(client) = self.clients.get(&id) {
+            let _ = client.tx.send(msg.encode());
+        }
+    }
+
+    fn users_frame(&self) -> ServerMessage {
+        ServerMessage::Users(self.users.iter().map(|u| u.nick.clone()).collect())
+    }
+
+    fn broadcast_users(&self) {
+        self.broadcast(&self.users_frame());
+    }
+
+    fn broadcast(&self, msg: &ServerMessage) {
+        let frame = msg.encode();
+        for client in self.clients.values() {
+            // A closed receiver means the session is on its way out and will
+            // disconnect itself.
+            let _ = client.tx.send(frame.clone());
+        }
+    }
+}
+
+/// Whether `a` and `b` were said in the same room, or between the same two
+/// users.
+fn same_conversation(a: &MessageData, b: &MessageData) -> bool {
+    Conversation::of(a) == Conversation::of(b)
+}
+
+/// How `data` goes out: direct messages have a frame of their own.
+fn message_frame(data: MessageData) -> ServerMessage {
+    if data.to.is_some() {
+        ServerMessage::Direct(data)
+    } else {
+        ServerMessage::Message(data)
+    }
+}
+
+/// An unguessable token for [`ClientMessage::Resume`].
+fn resume_token() -> String {
+    let mut bytes = [0; 16];
+    getrandom::fill(&mut bytes).expect("no source of randomness");
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Milliseconds since the Unix epoch.
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
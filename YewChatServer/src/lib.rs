@@ -0,0 +1,189 @@
+//! WebSocket chat server for YewChat.
+//!
+//! Speaks the frames defined in `yewchat-protocol`, the same ones the
+//! TypeScript `SimpleWebsocketServer` does: clients `register` a nickname and
+//! post `message`s, and the server relays every line to everyone and sends the
+//! full `users` list whenever someone joins or leaves.
+//!
+//! On top of that, clients may `join` and `leave` rooms and `post` to them;
+//! see [`Hub`]. Plain `message`s go to the lobby.
+//!
+//! A `register` is answered with `registerAck`, or with `registerRejected` and
+//! a reason when the nickname breaks the rules in
+//! [`validate_nick`](yewchat_protocol::validate_nick) or is already online.
+//!
+//! Clients first `POST` a nickname and secret to
+//! [`AUTH_PATH`](yewchat_protocol::AUTH_PATH) for a signed token (see
+//! [`Auth`]), then pass it as `?token=` when opening the socket. Sockets
+//! without a valid token are closed right away with [`CLOSE_TOKEN_REJECTED`],
+//! or [`CLOSE_TOKEN_EXPIRED`] if it ran out, and a session is closed with
+//! the latter when its token runs out.
+//!
+//! Every accepted nickname also gets a resume token, and every message an id.
+//! A client whose connection drops can `resume` within the [`Hub`]'s grace
+//! period to keep its nickname and have the messages it missed replayed.
+
+mod auth;
+mod hub;
+
+pub use auth::{Auth, AuthFailure, Claims, DEFAULT_TTL, DEV_INVITE};
+pub use hub::{ClientId, Hub, DEFAULT_GRACE, HISTORY_LEN};
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::extract::ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::http::{header, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use tokio::net::TcpListener;
+use tower_http::cors::{Any, CorsLayer};
+use yewchat_protocol::{
+    AuthError, AuthRequest, AUTH_PATH, CLOSE_TOKEN_EXPIRED, CLOSE_TOKEN_REJECTED,
+};
+
+#[derive(Clone)]
+struct ServerState {
+    hub: Hub,
+    auth: Arc<Auth>,
+}
+
+/// The server's routes, sharing `hub` between all connections and checking
+/// tokens with `auth`.
+pub fn app(hub: Hub, auth: Auth) -> Router {
+    // The client is usually served from another origin than the server.
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods([Method::POST])
+        .allow_headers([header::CONTENT_TYPE]);
+
+    Router::new()
+        .route("/", get(upgrade))
+        .route(AUTH_PATH, post(login).layer(cors))
+        .with_state(ServerState {
+            hub,
+            auth: Arc::new(auth),
+        })
+}
+
+/// Serves chat on `listener` until the process exits.
+pub async fn serve(listener: TcpListener, hub: Hub, auth: Auth) -> std::io::Result<()> {
+    axum::serve(listener, app(hub, auth)).await
+}
+
+async fn login(State(state): State<ServerState>, Json(request): Json<AuthRequest>) -> Response {
+    match state.auth.login(&request) {
+        Ok(token) => {
+            log::info!("issued a token for {:?}", request.nick);
+            Json(token).into_response()
+        }
+        Err(e) => {
+            log::info!("refused a token for {:?}: {}", request.nick, e);
+            let error = AuthError {
+                error: e.to_string(),
+            };
+            (StatusCode::UNAUTHORIZED, Json(error)).into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Handshake {
+    token: Option<String>,
+}
+
+async fn upgrade(
+    ws: WebSocketUpgrade,
+    State(state): State<ServerState>,
+    Query(handshake): Query<Handshake>,
+) -> Response {
+    let claims = handshake
+        .token
+        .ok_or(AuthFailure::BadToken)
+        .and_then(|token| state.auth.verify(&token));
+    ws.on_upgrade(move |socket| async move {
+        match claims {
+            Ok(claims) => session(socket, state.hub, claims).await,
+            Err(e) => refuse(socket, e).await,
+        }
+    })
+}
+
+/// Closes a socket whose token was not accepted, saying why.
+async fn refuse(mut socket: WebSocket, failure: AuthFailure) {
+    log::info!("refusing a socket: {}", failure);
+    let code = match failure {
+        AuthFailure::Expired => CLOSE_TOKEN_EXPIRED,
+        _ => CLOSE_TOKEN_REJECTED,
+    };
+    let close = CloseFrame {
+        code,
+        reason: failure.to_string().into(),
+    };
+    let _ = socket.send(Message::Close(Some(close))).await;
+}
+
+async fn session(socket: WebSocket, hub: Hub, claims: Claims) {
+    let (id, mut outbound) = hub.connect(claims.nick);
+    log::info!("client {} connected", id);
+    let (mut sink, mut stream) = socket.split();
+
+    // Resolves to whether the token ran out.
+    let mut writer = tokio::spawn(async move {
+        let expiry = tokio::time::sleep(until(claims.expires_at));
+        tokio::pin!(expiry);
+        loop {
+            tokio::select! {
+                frame = outbound.recv() => {
+                    let Some(frame) = frame else { break false };
+                    if sink.send(Message::Text(frame.into())).await.is_err() {
+                        break false;
+                    }
+                }
+                _ = &mut expiry => {
+                    let close = CloseFrame {
+                        code: CLOSE_TOKEN_EXPIRED,
+                        reason: "token expired".into(),
+                    };
+                    let _ = sink.send(Message::Close(Some(close))).await;
+                    break true;
+                }
+            }
+        }
+    });
+
+    // Whether the client said goodbye, rather than just vanishing.
+    let farewell = loop {
+        tokio::select! {
+            msg = stream.next() => match msg {
+                Some(Ok(Message::Text(text))) => hub.receive(id, text.as_str()),
+                Some(Ok(Message::Close(_))) => break true,
+                Some(Err(_)) | None => break false,
+                Some(Ok(_)) => {}
+            },
+            // The writer only stops once the socket is done for. An expired
+            // token leaves nothing to resume.
+            expired = &mut writer => break expired.unwrap_or(false),
+        }
+    };
+
+    if farewell {
+        hub.leave(id);
+    } else {
+        hub.disconnect(id);
+    }
+    writer.abort();
+    log::info!("client {} disconnected", id);
+}
+
+/// How long from now until `time`, in milliseconds since the Unix epoch.
+fn until(time: u64) -> Duration {
+    let target = UNIX_EPOCH + Duration::from_millis(time);
+    target
+        .duration_since(SystemTime::now())
+        .unwrap_or(Duration::ZERO)
+}
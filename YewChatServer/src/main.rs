@@ -0,0 +1,15 @@
+use tokio::net::TcpListener;
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let port: u16 = match std::env::var("PORT") {
+        Ok(port) => port.parse().expect("PORT must be a port number"),
+        Err(_) => 8080,
+    };
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    log::info!("Listening on port {}", port);
+    let hub = yewchat_server::Hub::default();
+    yewchat_server::serve(listener, hub, yewchat_server::Auth::from_env()).await
+}
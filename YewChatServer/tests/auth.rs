@@ -0,0 +1,88 @@
+//! The `/auth` endpoint, driven without a network through the router.
+
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::response::Response;
+use yewchat_protocol::{AuthError, AuthRequest, AuthToken, AUTH_PATH};
+use yewchat_server::{app, Auth, AuthFailure, Hub};
+
+use tower::ServiceExt;
+
+fn auth() -> Auth {
+    Auth::new(b"test key", Duration::from_secs(60))
+        .with_invite("undangan")
+        .with_password("Budi", "rahasia")
+}
+
+async fn post(request: &AuthRequest) -> Response {
+    let request = Request::post(AUTH_PATH)
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::to_vec(request).unwrap()))
+        .unwrap();
+    app(Hub::default(), auth()).oneshot(request).await.unwrap()
+}
+
+async fn body<T: serde::de::DeserializeOwned>(response: Response) -> T {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+fn request(nick: &str, secret: &str) -> AuthRequest {
+    AuthRequest {
+        nick: nick.into(),
+        secret: secret.into(),
+    }
+}
+
+#[tokio::test]
+async fn invite_codes_get_a_token_for_any_free_nick() {
+    let response = post(&request("alice", "undangan")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let token: AuthToken = body(response).await;
+    let claims = auth().verify(&token.token).unwrap();
+    assert_eq!(claims.nick, "alice");
+    assert_eq!(claims.expires_at, token.expires_at);
+}
+
+#[tokio::test]
+async fn accounts_need_their_password() {
+    let response = post(&request("budi", "rahasia")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // An invite code is not enough to take a nick with an account.
+    let response = post(&request("budi", "undangan")).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let error: AuthError = body(response).await;
+    assert_eq!(error.error, AuthFailure::WrongSecret.to_string());
+}
+
+#[tokio::test]
+async fn bad_secrets_and_nicks_are_refused_with_a_reason() {
+    for (nick, secret) in [("alice", "salah"), ("alice", ""), ("admin", "undangan")] {
+        let response = post(&request(nick, secret)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{}", nick);
+        let error: AuthError = body(response).await;
+        assert!(!error.error.is_empty());
+    }
+}
+
+#[test]
+fn tokens_are_tied_to_the_key_and_the_clock() {
+    let token = auth().issue("alice").token;
+    assert!(auth().verify(&token).is_ok());
+
+    let other = Auth::new(b"other key", Duration::from_secs(60));
+    assert_eq!(other.verify(&token), Err(AuthFailure::BadToken));
+
+    let mut tampered = token.clone();
+    tampered.insert(0, 'x');
+    assert_eq!(auth().verify(&tampered), Err(AuthFailure::BadToken));
+
+    let expired = Auth::new(b"test key", Duration::ZERO).issue("alice").token;
+    assert_eq!(auth().verify(&expired), Err(AuthFailure::Expired));
+}
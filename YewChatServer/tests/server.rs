@@ -0,0 +1,879 @@
+//! Drives the server over real localhost sockets.
+
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use yewchat_protocol::{
+    ClientMessage, DirectData, EditData, MembersData, NickError, PostData, ReactionData, ReadData,
+    ResumeData, ServerMessage, TypingData, CLOSE_TOKEN_EXPIRED, CLOSE_TOKEN_REJECTED, LOBBY,
+};
+use yewchat_server::{Auth, Hub};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// How long to wait for a frame that should arrive.
+const PATIENCE: Duration = Duration::from_secs(5);
+/// How long to wait before concluding a frame is not coming.
+const QUIET: Duration = Duration::from_millis(200);
+
+const KEY: &[u8] = b"test key";
+
+/// An authority the tests and the server share, so tests can mint tokens.
+fn auth(ttl: Duration) -> Auth {
+    Auth::new(KEY, ttl)
+}
+
+/// Starts a server on a free port and returns its URL.
+async fn start() -> String {
+    start_with(Hub::default(), auth(yewchat_server::DEFAULT_TTL)).await
+}
+
+async fn start_with(hub: Hub, auth: Auth) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(yewchat_server::serve(listener, hub, auth));
+    format!("ws://{}", addr)
+}
+
+/// Opens a socket with a token for `nick`, without registering.
+async fn connect(url: &str, nick: &str) -> Socket {
+    let token = auth(yewchat_server::DEFAULT_TTL).issue(nick).token;
+    connect_with(url, &format!("?token={}", token))
+        .await
+        .unwrap()
+}
+
+async fn connect_with(
+    url: &str,
+    query: &str,
+) -> Result<Socket, tokio_tungstenite::tungstenite::Error> {
+    Ok(
+        tokio_tungstenite::connect_async(format!("{}/{}", url, query))
+            .await?
+            .0,
+    )
+}
+
+/// Reads frames until the server closes the socket, returning its close code.
+async fn close_code(socket: &mut Socket) -> CloseCode {
+    loop {
+        let frame = timeout(PATIENCE, socket.next())
+            .await
+            .expect("socket was never closed")
+            .expect("socket closed without a close frame")
+            .unwrap();
+        if let Message::Close(close) = frame {
+            return close.expect("close frame without a code").code;
+        }
+    }
+}
+
+/// Connects and registers `nick`, consuming the ack and users frames that
+/// follow.
+async fn join(url: &str, nick: &str) -> Socket {
+    join_resumable(url, nick).await.0
+}
+
+/// Like [`join`], also returning the resume token.
+async fn join_resumable(url: &str, nick: &str) -> (Socket, String) {
+    let mut socket = connect(url, nick).await;
+    send(&mut socket, ClientMessage::Register(nick.into())).await;
+    let resume = welcome(&mut socket, nick).await;
+    assert!(matches!(recv(&mut socket).await, ServerMessage::Users(_)));
+    (socket, resume)
+}
+
+/// Consumes the ack for `nick` and returns the resume token after it.
+async fn welcome(socket: &mut Socket, nick: &str) -> String {
+    assert_eq!(recv(socket).await, ServerMessage::RegisterAck(nick.into()));
+    let ServerMessage::ResumeToken(resume) = recv(socket).await else {
+        panic!("expected a resume token");
+    };
+    resume
+}
+
+async fn resume(url: &str, nick: &str, token: &str, last_seen: Option<u64>) -> Socket {
+    let mut socket = connect(url, nick).await;
+    let resume = ResumeData {
+        token: token.into(),
+        last_seen,
+    };
+    send(&mut socket, ClientMessage::Resume(resume)).await;
+    socket
+}
+
+/// The text of the next frame, which must be a message.
+async fn recv_text(socket: &mut Socket) -> String {
+    let ServerMessage::Message(data) = recv(socket).await else {
+        panic!("expected a message");
+    };
+    data.message
+}
+
+async fn send(socket: &mut Socket, msg: ClientMessage) {
+    socket.send(Message::text(msg.encode())).await.unwrap();
+}
+
+async fn recv(socket: &mut Socket) -> ServerMessage {
+    loop {
+        let frame = timeout(PATIENCE, socket.next())
+            .await
+            .expect("timed out waiting for a frame")
+            .expect("socket closed")
+            .unwrap();
+        if let Message::Text(text) = frame {
+            return ServerMessage::decode(text.as_str()).unwrap();
+        }
+    }
+}
+
+async fn assert_quiet(socket: &mut Socket) {
+    if let Ok(frame) = timeout(QUIET, socket.next()).await {
+        panic!("unexpected frame: {:?}", frame);
+    }
+}
+
+fn rooms(names: &[&str]) -> ServerMessage {
+    ServerMessage::Rooms(names.iter().map(|n| n.to_string()).collect())
+}
+
+fn members(room: &str, names: &[&str]) -> ServerMessage {
+    ServerMessage::Members(MembersData {
+        room: room.into(),
+        users: names.iter().map(|n| n.to_string()).collect(),
+    })
+}
+
+fn post(room: &str, message: &str) -> ClientMessage {
+    ClientMessage::Post(PostData {
+        room: room.into(),
+        message: message.into(),
+        parent_id: None,
+    })
+}
+
+fn direct(to: &str, message: &str) -> ClientMessage {
+    ClientMessage::Direct(DirectData {
+        to: to.into(),
+        message: message.into(),
+        parent_id: None,
+    })
+}
+
+fn edit(id: u64, message: &str) -> ClientMessage {
+    ClientMessage::Edit(EditData {
+        id,
+        message: message.into(),
+    })
+}
+
+fn reaction(id: u64, emoji: &str, from: Option<&str>) -> ReactionData {
+    ReactionData {
+        id,
+        emoji: emoji.into(),
+        from: from.map(Into::into),
+    }
+}
+
+fn typing(room: &str, to: Option<&str>, from: Option<&str>, typing: bool) -> TypingData {
+    TypingData {
+        room: room.into(),
+        to: to.map(Into::into),
+        from: from.map(Into::into),
+        typing,
+    }
+}
+
+fn read(id: u64, from: Option<&str>) -> ReadData {
+    ReadData {
+        id,
+        from: from.map(Into::into),
+    }
+}
+
+fn users(names: &[&str]) -> ServerMessage {
+    ServerMessage::Users(names.iter().map(|n| n.to_string()).collect())
+}
+
+#[tokio::test]
+async fn register_broadcasts_the_user_list() {
+    let url = start().await;
+    let mut alice = connect(&url, "alice").await;
+    send(&mut alice, ClientMessage::Register("alice".into())).await;
+    welcome(&mut alice, "alice").await;
+    assert_eq!(recv(&mut alice).await, users(&["alice"]));
+
+    let mut bob = connect(&url, "bob").await;
+    send(&mut bob, ClientMessage::Register("bob".into())).await;
+    assert_eq!(recv(&mut alice).await, users(&["alice", "bob"]));
+    welcome(&mut bob, "bob").await;
+    assert_eq!(recv(&mut bob).await, users(&["alice", "bob"]));
+}
+
+#[tokio::test]
+async fn messages_reach_everyone_including_the_sender() {
+    let url = start().await;
+    let mut alice = join(&url, "alice").await;
+    let mut bob = join(&url, "bob").await;
+    recv(&mut alice).await; // bob joining
+
+    send(&mut alice, ClientMessage::Message("halo bob".into())).await;
+    for socket in [&mut alice, &mut bob] {
+        let ServerMessage::Message(data) = recv(socket).await else {
+            panic!("expected a message");
+        };
+        assert_eq!(data.id, Some(1));
+        assert_eq!(data.from, "alice");
+        assert_eq!(data.message, "halo bob");
+        assert!(data.time > 0);
+    }
+}
+
+#[tokio::test]
+async fn leaving_broadcasts_the_remaining_users() {
+    let url = start().await;
+    let mut alice = join(&url, "alice").await;
+    let mut bob = join(&url, "bob").await;
+    recv(&mut alice).await; // bob joining
+
+    bob.close(None).await.unwrap();
+    assert_eq!(recv(&mut alice).await, users(&["alice"]));
+}
+
+#[tokio::test]
+async fn unregistered_sockets_cannot_post() {
+    let url = start().await;
+    let mut alice = join(&url, "alice").await;
+    let mut lurker = connect(&url, "lurker").await;
+
+    send(&mut lurker, ClientMessage::Message("boo".into())).await;
+    assert_quiet(&mut alice).await;
+}
+
+#[tokio::test]
+async fn garbage_frames_are_dropped_without_closing_the_socket() {
+    let url = start().await;
+    let mut alice = join(&url, "alice").await;
+
+    for frame in ["not json", r#"{"messageType":"users","dataArray":[]}"#] {
+        alice.send(Message::text(frame)).await.unwrap();
+    }
+    send(&mut alice, ClientMessage::Message("masih hidup".into())).await;
+    let ServerMessage::Message(data) = recv(&mut alice).await else {
+        panic!("expected a message");
+    };
+    assert_eq!(data.message, "masih hidup");
+}
+
+#[tokio::test]
+async fn taken_nicks_are_rejected_in_any_case() {
+    let url = start().await;
+    let mut alice = join(&url, "alice").await;
+    let mut impostor = connect(&url, "Alice").await;
+
+    send(&mut impostor, ClientMessage::Register("Alice".into())).await;
+    assert_eq!(
+        recv(&mut impostor).await,
+        ServerMessage::RegisterRejected(NickError::Taken.to_string())
+    );
+    assert_quiet(&mut alice).await;
+
+    // The rejected socket stays open and may try again once the name is free.
+    alice.close(None).await.unwrap();
+    assert_eq!(recv(&mut impostor).await, users(&[]));
+    send(&mut impostor, ClientMessage::Register("Alice".into())).await;
+    welcome(&mut impostor, "Alice").await;
+}
+
+#[tokio::test]
+async fn invalid_nicks_are_rejected_with_the_rule_broken() {
+    let url = start().await;
+    let mut socket = connect(&url, "lurker").await;
+
+    for (nick, err) in [
+        ("a", NickError::TooShort),
+        ("al ice", NickError::BadCharacter(' ')),
+        ("admin", NickError::Reserved),
+    ] {
+        send(&mut socket, ClientMessage::Register(nick.into())).await;
+        assert_eq!(
+            recv(&mut socket).await,
+            ServerMessage::RegisterRejected(err.to_string())
+        );
+    }
+
+    // Never registered, so it still cannot post.
+    send(&mut socket, ClientMessage::Message("boo".into())).await;
+    assert_quiet(&mut socket).await;
+}
+
+#[tokio::test]
+async fn a_nick_is_free_again_once_its_owner_leaves() {
+    let url = start().await;
+    let mut alice = join(&url, "alice").await;
+    let mut bob = join(&url, "bob").await;
+
+    alice.close(None).await.unwrap();
+    assert_eq!(recv(&mut bob).await, users(&["bob"]));
+    join(&url, "alice").await;
+}
+
+#[tokio::test]
+async fn sockets_without_a_valid_token_are_refused() {
+    let url = start().await;
+    let forged = Auth::new(b"another key", yewchat_server::DEFAULT_TTL).issue("alice");
+    let expired = auth(Duration::ZERO).issue("alice");
+
+    for (query, code) in [
+        (String::new(), CLOSE_TOKEN_REJECTED),
+        ("?token=garbage".into(), CLOSE_TOKEN_REJECTED),
+        (format!("?token={}", forged.token), CLOSE_TOKEN_REJECTED),
+        (format!("?token={}", expired.token), CLOSE_TOKEN_EXPIRED),
+    ] {
+        let mut socket = connect_with(&url, &query).await.unwrap();
+        assert_eq!(
+            close_code(&mut socket).await,
+            CloseCode::from(code),
+            "{}",
+            query
+        );
+    }
+}
+
+#[tokio::test]
+async fn only_the_nick_on_the_token_can_be_registered() {
+    let url = start().await;
+    let mut socket = connect(&url, "alice").await;
+
+    send(&mut socket, ClientMessage::Register("bob".into())).await;
+    assert!(matches!(
+        recv(&mut socket).await,
+        ServerMessage::RegisterRejected(_)
+    ));
+    send(&mut socket, ClientMessage::Register("alice".into())).await;
+    welcome(&mut socket, "alice").await;
+}
+
+#[tokio::test]
+async fn sessions_end_when_their_token_expires() {
+    let url = start_with(Hub::default(), auth(Duration::from_millis(500))).await;
+    let token = auth(Duration::from_millis(500)).issue("alice").token;
+    let mut socket = connect_with(&url, &format!("?token={}", token))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        close_code(&mut socket).await,
+        CloseCode::from(CLOSE_TOKEN_EXPIRED)
+    );
+}
+
+#[tokio::test]
+async fn a_dropped_user_resumes_without_anyone_noticing() {
+    let url = start().await;
+    let (alice, token) = join_resumable(&url, "alice").await;
+    let mut bob = join(&url, "bob").await;
+    send(&mut bob, ClientMessage::Message("satu".into())).await;
+    let seen = match recv(&mut bob).await {
+        ServerMessage::Message(data) => data.id,
+        msg => panic!("expected a message, got {:?}", msg),
+    };
+
+    // Gone without a close frame: alice stays on the list.
+    drop(alice);
+    send(&mut bob, ClientMessage::Message("dua".into())).await;
+    send(&mut bob, ClientMessage::Message("tiga".into())).await;
+    assert_eq!(recv_text(&mut bob).await, "dua");
+    assert_eq!(recv_text(&mut bob).await, "tiga");
+
+    let mut alice = resume(&url, "alice", &token, seen).await;
+    welcome(&mut alice, "alice").await;
+    assert_eq!(recv_text(&mut alice).await, "dua");
+    assert_eq!(recv_text(&mut alice).await, "tiga");
+    assert_eq!(recv(&mut alice).await, users(&["alice", "bob"]));
+    assert_quiet(&mut bob).await;
+}
+
+#[tokio::test]
+async fn resuming_replaces_a_socket_that_has_not_noticed_it_is_dead() {
+    let url = start().await;
+    let (mut stale, token) = join_resumable(&url, "alice").await;
+
+    let mut alice = resume(&url, "alice", &token, None).await;
+    welcome(&mut alice, "alice").await;
+    assert_eq!(recv(&mut alice).await, users(&["alice"]));
+
+    let closed = timeout(PATIENCE, async {
+        while let Some(Ok(frame)) = stale.next().await {
+            assert!(matches!(frame, Message::Close(_)), "{:?}", frame);
+        }
+    });
+    closed.await.expect("stale socket stayed open");
+}
+
+#[tokio::test]
+async fn a_held_nick_is_released_after_the_grace_period() {
+    let url = start_with(
+        Hub::new(Duration::from_millis(200)),
+        auth(yewchat_server::DEFAULT_TTL),
+    )
+    .await;
+    let (alice, token) = join_resumable(&url, "alice").await;
+    let mut bob = join(&url, "bob").await;
+
+    drop(alice);
+    assert_eq!(recv(&mut bob).await, users(&["bob"]));
+
+    // Too late to resume: the socket registers afresh instead.
+    let mut alice = resume(&url, "alice", &token, None).await;
+    let fresh = welcome(&mut alice, "alice").await;
+    assert_ne!(fresh, token);
+    assert_eq!(recv(&mut bob).await, users(&["bob", "alice"]));
+}
+
+#[tokio::test]
+async fn every_resume_starts_a_new_grace_period() {
+    let grace = Duration::from_secs(1);
+    let url = start_with(Hub::new(grace), auth(yewchat_server::DEFAULT_TTL)).await;
+    let (alice, token) = join_resumable(&url, "alice").await;
+    let mut bob = join(&url, "bob").await;
+
+    drop(alice);
+    tokio::time::sleep(grace / 2).await;
+    let mut alice = resume(&url, "alice", &token, None).await;
+    let token = welcome(&mut alice, "alice").await;
+    recv(&mut alice).await;
+    drop(alice);
+
+    // Past the first hold, but not the second.
+    tokio::time::sleep(grace * 3 / 4).await;
+    let mut alice = resume(&url, "alice", &token, None).await;
+    welcome(&mut alice, "alice").await;
+    assert_eq!(recv(&mut alice).await, users(&["alice", "bob"]));
+    assert_quiet(&mut bob).await;
+}
+
+#[tokio::test]
+async fn resume_tokens_only_work_for_their_own_nick() {
+    let url = start().await;
+    let (_alice, token) = join_resumable(&url, "alice").await;
+
+    let mut mallory = resume(&url, "mallory", &token, None).await;
+    welcome(&mut mallory, "mallory").await;
+    assert_eq!(recv(&mut mallory).await, users(&["alice", "mallory"]));
+}
+
+#[tokio::test]
+async fn room_messages_only_reach_room_members() {
+    let url = start().await;
+    let mut alice = join(&url, "alice").await;
+    let mut bob = join(&url, "bob").await;
+    let mut carol = join(&url, "carol").await;
+    recv(&mut alice).await; // bob joining
+    recv(&mut alice).await; // carol joining
+    recv(&mut bob).await; // carol joining
+
+    send(&mut alice, ClientMessage::Join("rust".into())).await;
+    for socket in [&mut alice, &mut bob, &mut carol] {
+        assert_eq!(recv(socket).await, rooms(&[LOBBY, "rust"]));
+    }
+    assert_eq!(recv(&mut alice).await, members("rust", &["alice"]));
+    send(&mut bob, ClientMessage::Join("rust".into())).await;
+    for socket in [&mut alice, &mut bob] {
+        assert_eq!(recv(socket).await, members("rust", &["alice", "bob"]));
+    }
+
+    send(&mut alice, post("rust", "halo rustacean")).await;
+    for socket in [&mut alice, &mut bob] {
+        let ServerMessage::Message(data) = recv(socket).await else {
+            panic!("expected a message");
+        };
+        assert_eq!(data.room, "rust");
+        assert_eq!(data.message, "halo rustacean");
+    }
+    assert_quiet(&mut carol).await;
+
+    // Not a member, so not allowed to post there either.
+    send(&mut carol, post("rust", "boo")).await;
+    assert_quiet(&mut alice).await;
+}
+
+#[tokio::test]
+async fn everyone_is_in_the_lobby() {
+    let url = start().await;
+    let mut alice = join(&url, "alice").await;
+    let mut bob = join(&url, "bob").await;
+    recv(&mut alice).await; // bob joining
+
+    send(&mut bob, ClientMessage::Join(LOBBY.into())).await;
+    assert_eq!(recv(&mut bob).await, members(LOBBY, &["alice", "bob"]));
+    send(&mut bob, ClientMessage::Leave(LOBBY.into())).await;
+    send(&mut alice, post(LOBBY, "masih di sini?")).await;
+    assert_eq!(recv_text(&mut bob).await, "masih di sini?");
+}
+
+#[tokio::test]
+async fn empty_rooms_go_away() {
+    let url = start().await;
+    let mut alice = join(&url, "alice").await;
+    let mut bob = join(&url, "bob").await;
+    recv(&mut alice).await; // bob joining
+
+    send(&mut alice, ClientMessage::Join("rust".into())).await;
+    recv(&mut alice).await; // rooms
+    recv(&mut alice).await; // members
+    send(&mut bob, ClientMessage::Join("rust".into())).await;
+    recv(&mut alice).await; // bob joining rust
+    send(&mut bob, ClientMessage::ListRooms).await;
+    recv(&mut bob).await; // rooms, broadcast when rust was created
+    recv(&mut bob).await; // members
+    assert_eq!(recv(&mut bob).await, rooms(&[LOBBY, "rust"]));
+
+    send(&mut bob, ClientMessage::Leave("rust".into())).await;
+    assert_eq!(recv(&mut alice).await, members("rust", &["alice"]));
+    // Leaving the server leaves its rooms too.
+    alice.close(None).await.unwrap();
+    assert_eq!(recv(&mut bob).await, rooms(&[LOBBY]));
+    assert_eq!(recv(&mut bob).await, users(&["bob"]));
+}
+
+#[tokio::test]
+async fn direct_messages_only_reach_their_two_ends() {
+    let url = start().await;
+    let mut alice = join(&url, "alice").await;
+    let mut bob = join(&url, "bob").await;
+    let mut carol = join(&url, "carol").await;
+    recv(&mut alice).await; // bob joining
+    recv(&mut alice).await; // carol joining
+    recv(&mut bob).await; // carol joining
+
+    send(&mut alice, direct("bob", "psst")).await;
+    for socket in [&mut alice, &mut bob] {
+        let ServerMessage::Direct(data) = recv(socket).await else {
+            panic!("expected a direct message");
+        };
+        assert_eq!(data.from, "alice");
+        assert_eq!(data.to.as_deref(), Some("bob"));
+        assert_eq!(data.message, "psst");
+    }
+    assert_quiet(&mut carol).await;
+
+    // Nobody by that name, and no talking to yourself: only alice hears.
+    for to in ["dave", "alice"] {
+        send(&mut alice, direct(to, "halo?")).await;
+        let ServerMessage::DirectRejected(rejected) = recv(&mut alice).await else {
+            panic!("expected the direct message back");
+        };
+        assert_eq!(rejected.direct.to, to);
+        assert_eq!(rejected.direct.message, "halo?");
+        assert!(!rejected.reason.is_empty());
+    }
+    assert_quiet(&mut bob).await;
+    assert_quiet(&mut carol).await;
+}
+
+#[tokio::test]
+async fn direct_messages_wait_for_a_dropped_recipient() {
+    let url = start().await;
+    let (bob, token) = join_resumable(&url, "bob").await;
+    let mut alice = join(&url, "alice").await;
+
+    drop(bob);
+    send(&mut alice, direct("bob", "masih di sana?")).await;
+    assert!(matches!(recv(&mut alice).await, ServerMessage::Direct(_)));
+
+    let mut bob = resume(&url, "bob", &token, None).await;
+    welcome(&mut bob, "bob").await;
+    let ServerMessage::Direct(data) = recv(&mut bob).await else {
+        panic!("expected the missed direct message");
+    };
+    assert_eq!(data.message, "masih di sana?");
+}
+
+#[tokio::test]
+async fn replies_are_filed_under_the_thread_root() {
+    let url = start().await;
+    let mut alice = join(&url, "alice").await;
+
+    send(&mut alice, post(LOBBY, "ada yang pakai yew?")).await;
+    recv(&mut alice).await;
+    let reply = |parent_id| {
+        ClientMessage::Post(PostData {
+            room: LOBBY.into(),
+            message: "aku".into(),
+            parent_id: Some(parent_id),
+        })
+    };
+    send(&mut alice, reply(1)).await;
+    // Replying to a reply lands in the same thread.
+    send(&mut alice, reply(2)).await;
+    for id in [2, 3] {
+        let ServerMessage::Message(data) = recv(&mut alice).await else {
+            panic!("expected a message");
+        };
+        assert_eq!((data.id, data.parent_id), (Some(id), Some(1)));
+    }
+
+    // Neither messages that do not exist nor ones from another room.
+    send(&mut alice, reply(99)).await;
+    send(&mut alice, ClientMessage::Join("rust".into())).await;
+    recv(&mut alice).await; // rooms
+    recv(&mut alice).await; // members
+    send(
+        &mut alice,
+        ClientMessage::Post(PostData {
+            room: "rust".into(),
+            message: "salah ruang".into(),
+            parent_id: Some(1),
+        }),
+    )
+    .await;
+    assert_quiet(&mut alice).await;
+}
+
+#[tokio::test]
+async fn only_authors_edit_and_delete_their_messages() {
+    let url = start().await;
+    let mut alice = join(&url, "alice").await;
+    let mut bob = join(&url, "bob").await;
+    recv(&mut alice).await; // bob joining
+
+    send(&mut alice, post(LOBBY, "halo smua")).await;
+    recv(&mut alice).await;
+    recv(&mut bob).await;
+
+    // Not bob's to change.
+    send(&mut bob, edit(1, "dibajak")).await;
+    send(&mut bob, ClientMessage::Delete(1)).await;
+    assert_quiet(&mut alice).await;
+
+    send(&mut alice, edit(1, "halo semua")).await;
+    for socket in [&mut alice, &mut bob] {
+        let ServerMessage::Edit(data) = recv(socket).await else {
+            panic!("expected an edit");
+        };
+        assert_eq!(data.message, "halo semua");
+        assert!(data.edited.is_some());
+        assert_eq!(data.edits.len(), 1);
+        assert_eq!(data.edits[0].message, "halo smua");
+    }
+
+    send(&mut alice, ClientMessage::Delete(1)).await;
+    for socket in [&mut alice, &mut bob] {
+        assert_eq!(recv(socket).await, ServerMessage::Delete(1));
+    }
+    // Tombstones stay dead.
+    send(&mut alice, edit(1, "bangkit")).await;
+    assert_quiet(&mut bob).await;
+}
+
+#[tokio::test]
+async fn replays_carry_the_latest_version() {
+    let url = start().await;
+    let (bob, token) = join_resumable(&url, "bob").await;
+    let mut alice = join(&url, "alice").await;
+
+    drop(bob);
+    send(&mut alice, post(LOBBY, "satu")).await;
+    send(&mut alice, post(LOBBY, "dua")).await;
+    send(&mut alice, edit(1, "satu!")).await;
+    send(&mut alice, ClientMessage::Delete(2)).await;
+    for _ in 0..4 {
+        recv(&mut alice).await;
+    }
+
+    let mut bob = resume(&url, "bob", &token, None).await;
+    welcome(&mut bob, "bob").await;
+    let ServerMessage::Message(first) = recv(&mut bob).await else {
+        panic!("expected a message");
+    };
+    assert_eq!((first.message.as_str(), first.edits.len()), ("satu!", 1));
+    let ServerMessage::Message(second) = recv(&mut bob).await else {
+        panic!("expected a message");
+    };
+    assert!(second.deleted);
+    assert_eq!(second.message, "");
+}
+
+#[tokio::test]
+async fn replays_carry_changes_to_messages_seen_before() {
+    let url = start().await;
+    let (mut bob, token) = join_resumable(&url, "bob").await;
+    let mut alice = join(&url, "alice").await;
+    recv(&mut bob).await; // alice joining
+    send(&mut alice, post(LOBBY, "satu")).await;
+    send(&mut alice, post(LOBBY, "dua")).await;
+    assert_eq!(recv_text(&mut bob).await, "satu");
+    assert_eq!(recv_text(&mut bob).await, "dua");
+
+    drop(bob);
+    send(&mut alice, edit(1, "satu!")).await;
+    send(&mut alice, ClientMessage::React(reaction(2, "👍", None))).await;
+    send(&mut alice, post(LOBBY, "tiga")).await;
+    for _ in 0..5 {
+        recv(&mut alice).await;
+    }
+
+    let mut bob = resume(&url, "bob", &token, Some(2)).await;
+    welcome(&mut bob, "bob").await;
+    let ServerMessage::Edit(first) = recv(&mut bob).await else {
+        panic!("expected the edited message");
+    };
+    assert_eq!(first.message, "satu!");
+    let ServerMessage::Edit(second) = recv(&mut bob).await else {
+        panic!("expected the message reacted to");
+    };
+    assert_eq!(second.reactions["👍"], ["alice"]);
+    assert_eq!(recv_text(&mut bob).await, "tiga");
+    assert_eq!(recv(&mut bob).await, users(&["bob", "alice"]));
+}
+
+#[tokio::test]
+async fn reactions_are_counted_once_per_user() {
+    let url = start().await;
+    let mut alice = join(&url, "alice").await;
+    let mut bob = join(&url, "bob").await;
+    recv(&mut alice).await; // bob joining
+
+    send(&mut alice, post(LOBBY, "rilis!")).await;
+    recv(&mut alice).await;
+    recv(&mut bob).await;
+
+    send(&mut bob, ClientMessage::React(reaction(1, "🎉", None))).await;
+    // Twice is still once, and only the palette goes.
+    send(&mut bob, ClientMessage::React(reaction(1, "🎉", None))).await;
+    send(&mut bob, ClientMessage::React(reaction(1, "🦀", None))).await;
+    send(
+        &mut bob,
+        ClientMessage::Unreact(reaction(1, "🎉", Some("alice"))),
+    )
+    .await;
+    assert_eq!(
+        recv(&mut alice).await,
+        ServerMessage::React(reaction(1, "🎉", Some("bob")))
+    );
+    assert_eq!(
+        recv(&mut alice).await,
+        ServerMessage::Unreact(reaction(1, "🎉", Some("bob")))
+    );
+    assert_quiet(&mut alice).await;
+}
+
+#[tokio::test]
+async fn replays_carry_reactions() {
+    let url = start().await;
+    let (bob, token) = join_resumable(&url, "bob").await;
+    let mut alice = join(&url, "alice").await;
+
+    drop(bob);
+    send(&mut alice, post(LOBBY, "rilis!")).await;
+    send(&mut alice, ClientMessage::React(reaction(1, "👍", None))).await;
+    recv(&mut alice).await;
+    recv(&mut alice).await;
+
+    let mut bob = resume(&url, "bob", &token, None).await;
+    welcome(&mut bob, "bob").await;
+    let ServerMessage::Message(data) = recv(&mut bob).await else {
+        panic!("expected a message");
+    };
+    assert_eq!(data.reactions["👍"], ["alice"]);
+}
+
+#[tokio::test]
+async fn typing_reaches_the_others_in_the_conversation() {
+    let url = start().await;
+    let mut alice = join(&url, "alice").await;
+    let mut bob = join(&url, "bob").await;
+    let mut carol = join(&url, "carol").await;
+    recv(&mut alice).await; // bob joining
+    recv(&mut alice).await; // carol joining
+    recv(&mut bob).await; // carol joining
+
+    send(
+        &mut alice,
+        ClientMessage::Typing(typing(LOBBY, None, None, true)),
+    )
+    .await;
+    for socket in [&mut bob, &mut carol] {
+        assert_eq!(
+            recv(socket).await,
+            ServerMessage::Typing(typing(LOBBY, None, Some("alice"), true))
+        );
+    }
+
+    send(
+        &mut alice,
+        ClientMessage::Typing(typing(LOBBY, Some("bob"), Some("carol"), false)),
+    )
+    .await;
+    assert_eq!(
+        recv(&mut bob).await,
+        ServerMessage::Typing(typing(LOBBY, Some("bob"), Some("alice"), false))
+    );
+
+    // Not in the room, and not to themselves.
+    send(
+        &mut alice,
+        ClientMessage::Typing(typing("rust", None, None, true)),
+    )
+    .await;
+    send(
+        &mut alice,
+        ClientMessage::Typing(typing(LOBBY, Some("alice"), None, true)),
+    )
+    .await;
+    for socket in [&mut alice, &mut bob, &mut carol] {
+        assert_quiet(socket).await;
+    }
+}
+
+#[tokio::test]
+async fn read_markers_only_move_forward() {
+    let url = start().await;
+    let mut alice = join(&url, "alice").await;
+    let mut bob = join(&url, "bob").await;
+    recv(&mut alice).await; // bob joining
+
+    for text in ["satu", "dua"] {
+        send(&mut alice, post(LOBBY, text)).await;
+        recv(&mut alice).await;
+        recv(&mut bob).await;
+    }
+
+    send(&mut bob, ClientMessage::Read(read(2, None))).await;
+    // Behind the marker, or not there at all.
+    send(&mut bob, ClientMessage::Read(read(1, None))).await;
+    send(&mut bob, ClientMessage::Read(read(9, Some("alice")))).await;
+    for socket in [&mut alice, &mut bob] {
+        assert_eq!(
+            recv(socket).await,
+            ServerMessage::Read(read(2, Some("bob")))
+        );
+        assert_quiet(socket).await;
+    }
+}
+
+#[tokio::test]
+async fn read_markers_are_repeated_on_resume() {
+    let url = start().await;
+    let (alice, token) = join_resumable(&url, "alice").await;
+    let mut bob = join(&url, "bob").await;
+
+    send(&mut bob, post(LOBBY, "halo")).await;
+    send(&mut bob, ClientMessage::Read(read(1, None))).await;
+    recv(&mut bob).await;
+    recv(&mut bob).await;
+    drop(alice);
+
+    let mut alice = resume(&url, "alice", &token, Some(1)).await;
+    welcome(&mut alice, "alice").await;
+    assert_eq!(
+        recv(&mut alice).await,
+        ServerMessage::Read(read(1, Some("bob")))
+    );
+}